@@ -0,0 +1,374 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::level::{MAP_HEIGHT, MAP_WIDTH};
+use crate::rules::WallRule;
+use crate::{snake_cell, Direction, GameData, ItemType};
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+pub trait Agent {
+    fn decide(&self, view: &GameView) -> Option<Direction>; //这一步要走的方向,返回None时保持原来的方向
+}
+
+pub struct GameView<'a> {//交给Agent的只读游戏状态
+    data: &'a GameData,
+    snake: usize //Agent控制的是第几条蛇
+}
+
+impl<'a> GameView<'a> {
+    pub fn new(data: &'a GameData, snake: usize) -> GameView<'a> {
+        GameView { data: data, snake: snake }
+    }
+
+    pub fn head(&self) -> (usize, usize) {//自己蛇头所在的格子
+        snake_cell(&self.data.snakes[self.snake].body[0])
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.data.snakes[self.snake].body[0].direction
+    }
+
+    pub fn snakes(&self) -> Vec<Vec<(usize, usize)>> {//所有活着的蛇占据的格子,第一个是蛇头
+        self.data.snakes.iter().filter(|snake| snake.alive).map(|snake| {
+            snake.body[..snake.length as usize - 1].iter().map(snake_cell).collect()
+        }).collect()
+    }
+
+    pub fn item(&self, x: usize, y: usize) -> ItemType {
+        self.data.map[x][y]
+    }
+
+    pub fn is_food(&self, x: usize, y: usize) -> bool {
+        match self.item(x, y) {
+            ItemType::Item(kind) => self.data.items[kind].grows(),
+            _ => false
+        }
+    }
+
+    pub fn is_harmful(&self, x: usize, y: usize) -> bool {//墙和会扣生命值的物品
+        match self.item(x, y) {
+            ItemType::Wall => true,
+            ItemType::Item(kind) => self.data.items[kind].damages(),
+            ItemType::None => false
+        }
+    }
+
+    pub fn heals(&self, x: usize, y: usize) -> bool {
+        match self.item(x, y) {
+            ItemType::Item(kind) => self.data.items[kind].heals(),
+            _ => false
+        }
+    }
+
+    pub fn hp(&self) -> i8 {
+        self.data.stats[self.data.snakes[self.snake].stats].hp
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.data.tick
+    }
+
+    pub fn random(&self, n: usize) -> usize {//由种子、步数和蛇决定的随机数,同样的种子得到同样的结果
+        let mut x = self.data.seed ^ ((self.data.tick as u64) << 20) ^ ((self.snake as u64) << 52);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        ((x ^ (x >> 31)) % n as u64) as usize
+    }
+
+    pub fn free_wrap(&self) -> bool {//穿过四周的隔墙是否没有惩罚
+        self.data.rules.wall_for(&self.data.stage) == WallRule::FreeWrap
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum AgentKind {
+    Random, //随便走,只避开眼前的障碍
+    Greedy, //朝最近的食物直接走过去
+    Bfs, //广度优先搜索去食物的最短路径
+    AStar, //用A*算法找到去食物的最短路径
+    Hamilton //沿着走遍整个地图的环路走,不会撞到自己
+}
+
+impl AgentKind {
+    pub fn parse(text: &str) -> Option<AgentKind> {
+        match text {
+            "random" => Some(AgentKind::Random),
+            "greedy" => Some(AgentKind::Greedy),
+            "bfs" => Some(AgentKind::Bfs),
+            "astar" => Some(AgentKind::AStar),
+            "hamilton" => Some(AgentKind::Hamilton),
+            _ => None
+        }
+    }
+
+    pub fn key(&self) -> &'static str {//与parse对应的名字
+        match self {
+            AgentKind::Random => "random",
+            AgentKind::Greedy => "greedy",
+            AgentKind::Bfs => "bfs",
+            AgentKind::AStar => "astar",
+            AgentKind::Hamilton => "hamilton"
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgentKind::Random => "随机",
+            AgentKind::Greedy => "贪心",
+            AgentKind::Bfs => "广搜",
+            AgentKind::AStar => "寻路",
+            AgentKind::Hamilton => "环路"
+        }
+    }
+
+    pub fn next(&self) -> AgentKind {
+        match self {
+            AgentKind::Random => AgentKind::Greedy,
+            AgentKind::Greedy => AgentKind::Bfs,
+            AgentKind::Bfs => AgentKind::AStar,
+            AgentKind::AStar => AgentKind::Hamilton,
+            AgentKind::Hamilton => AgentKind::Random
+        }
+    }
+
+    pub fn agent(&self) -> Box<dyn Agent> {
+        match self {
+            AgentKind::Random => Box::new(RandomAgent),
+            AgentKind::Greedy => Box::new(GreedyAgent),
+            AgentKind::Bfs => Box::new(SearchAgent { heuristic: false }),
+            AgentKind::AStar => Box::new(SearchAgent { heuristic: true }),
+            AgentKind::Hamilton => Box::new(HamiltonAgent)
+        }
+    }
+}
+
+struct Board {
+    blocked: [[bool; MAP_HEIGHT]; MAP_WIDTH], //墙、伤害物品和所有的蛇身
+    targets: Vec<(usize, usize)>, //要去吃的物品
+    wrap: bool //能否免费穿过四周的隔墙
+}
+
+impl Board {
+    fn new(view: &GameView) -> Board {
+        let mut board = Board {
+            blocked: [[false; MAP_HEIGHT]; MAP_WIDTH],
+            targets: Vec::new(),
+            wrap: view.free_wrap()
+        };
+        let hurt = view.hp() < 3; //生命值不多时也去吃能加生命值的物品
+        for x in 0..MAP_WIDTH {
+            for y in 0..MAP_HEIGHT {
+                board.blocked[x][y] = view.is_harmful(x, y);
+                if view.is_food(x, y) || (hurt && view.heals(x, y)) {
+                    board.targets.push((x, y));
+                }
+            }
+        }
+        for snake in view.snakes() {
+            for (x, y) in snake {
+                board.blocked[x][y] = true;
+            }
+        }
+        board
+    }
+
+    fn neighbor(&self, cell: (usize, usize), direction: Direction) -> Option<(usize, usize)> {//相邻的格子,不能穿墙时出界返回None
+        let (dx, dy) = match direction {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0)
+        };
+        let x = cell.0 as i32 + dx;
+        let y = cell.1 as i32 + dy;
+        if !self.wrap && (x < 0 || y < 0 || x >= MAP_WIDTH as i32 || y >= MAP_HEIGHT as i32) {
+            return None;
+        }
+        Some((x.rem_euclid(MAP_WIDTH as i32) as usize, y.rem_euclid(MAP_HEIGHT as i32) as usize))
+    }
+
+    fn distance(&self, a: (usize, usize), b: (usize, usize)) -> usize {//两格之间的步数下限
+        let dx = (a.0 as i32 - b.0 as i32).abs() as usize;
+        let dy = (a.1 as i32 - b.1 as i32).abs() as usize;
+        if self.wrap {
+            dx.min(MAP_WIDTH - dx) + dy.min(MAP_HEIGHT - dy)
+        } else {
+            dx + dy
+        }
+    }
+
+    fn safe(&self, view: &GameView) -> Vec<Direction> {//下一步不会撞到东西的方向
+        let head = view.head();
+        DIRECTIONS.iter().cloned()
+            .filter(|direction| *direction != opposite(view.direction()))
+            .filter(|direction| self.neighbor(head, *direction).map_or(false, |(x, y)| !self.blocked[x][y]))
+            .collect()
+    }
+
+    fn closest(&self, safe: &[Direction], head: (usize, usize)) -> Option<Direction> {//朝离最近的目标最近的方向走
+        let target = match self.targets.iter().min_by_key(|target| self.distance(head, **target)) {
+            Some(target) => *target,
+            None => return safe.first().cloned()
+        };
+        safe.iter().cloned().min_by_key(|direction| self.distance(self.neighbor(head, *direction).unwrap(), target))
+    }
+}
+
+fn opposite(direction: Direction) -> Direction {
+    match direction {
+        Direction::Up => Direction::Down,
+        Direction::Down => Direction::Up,
+        Direction::Left => Direction::Right,
+        Direction::Right => Direction::Left
+    }
+}
+
+struct RandomAgent;
+
+impl Agent for RandomAgent {
+    fn decide(&self, view: &GameView) -> Option<Direction> {//每隔几步或者前方有障碍时随机转向
+        let safe = Board::new(view).safe(view);
+        if safe.is_empty() {
+            return None;
+        }
+        if safe.contains(&view.direction()) && view.tick() % 4 != 0 {
+            return Some(view.direction());
+        }
+        Some(safe[view.random(safe.len())])
+    }
+}
+
+struct GreedyAgent;
+
+impl Agent for GreedyAgent {
+    fn decide(&self, view: &GameView) -> Option<Direction> {
+        let board = Board::new(view);
+        board.closest(&board.safe(view), view.head())
+    }
+}
+
+struct SearchAgent {
+    heuristic: bool //为true时是A*,否则是广度优先搜索
+}
+
+impl Agent for SearchAgent {
+    fn decide(&self, view: &GameView) -> Option<Direction> {//沿最短路径走向最近的目标,找不到路径时退回贪心
+        let board = Board::new(view);
+        let safe = board.safe(view);
+        self.search(&board, view.head()).filter(|direction| safe.contains(direction))
+            .or_else(|| board.closest(&safe, view.head()))
+    }
+}
+
+impl SearchAgent {
+    fn search(&self, board: &Board, start: (usize, usize)) -> Option<Direction> {//返回去最近目标的最短路径的第一步
+        if board.targets.is_empty() {
+            return None;
+        }
+        let estimate = |cell: (usize, usize)| if self.heuristic {
+            board.targets.iter().map(|target| board.distance(cell, *target)).min().unwrap()
+        } else {
+            0
+        };
+        let mut first: [[Option<Direction>; MAP_HEIGHT]; MAP_WIDTH] = [[None; MAP_HEIGHT]; MAP_WIDTH]; //到达每一格时走的第一步
+        let mut cost = [[usize::max_value(); MAP_HEIGHT]; MAP_WIDTH];
+        let mut open = BinaryHeap::new();
+        cost[start.0][start.1] = 0;
+        open.push(Reverse((estimate(start), 0, start)));
+        while let Some(Reverse((_, steps, cell))) = open.pop() {
+            if steps > cost[cell.0][cell.1] {
+                continue;
+            }
+            if board.targets.contains(&cell) {
+                return first[cell.0][cell.1];
+            }
+            for direction in DIRECTIONS.iter() {
+                let next = match board.neighbor(cell, *direction) {
+                    Some(next) => next,
+                    None => continue
+                };
+                if board.blocked[next.0][next.1] || steps + 1 >= cost[next.0][next.1] {
+                    continue;
+                }
+                cost[next.0][next.1] = steps + 1;
+                first[next.0][next.1] = if cell == start { Some(*direction) } else { first[cell.0][cell.1] };
+                open.push(Reverse((steps + 1 + estimate(next), steps + 1, next)));
+            }
+        }
+        None
+    }
+}
+
+struct HamiltonAgent;
+
+impl Agent for HamiltonAgent {
+    fn decide(&self, view: &GameView) -> Option<Direction> {//沿环路走,环路被挡住时退回贪心
+        let board = Board::new(view);
+        let safe = board.safe(view);
+        let (x, y) = view.head();
+        let next = if y == 0 { //第0行从右往左走回起点
+            if x > 0 { (x - 1, 0) } else { (0, 1) }
+        } else if x % 2 == 0 { //偶数列往下走
+            if y < MAP_HEIGHT - 1 { (x, y + 1) } else { (x + 1, y) }
+        } else if y > 1 { //奇数列往上走
+            (x, y - 1)
+        } else if x == MAP_WIDTH - 1 {
+            (x, 0)
+        } else {
+            (x + 1, 1)
+        };
+        safe.iter().cloned().find(|direction| board.neighbor((x, y), *direction) == Some(next))
+            .or_else(|| board.closest(&safe, (x, y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::builtin_items;
+    use crate::level::Level;
+    use crate::{load_stage, test_game};
+
+    const AGENTS: [AgentKind; 5] = [AgentKind::Random, AgentKind::Greedy, AgentKind::Bfs, AgentKind::AStar, AgentKind::Hamilton];
+
+    fn board(walls: &[(usize, usize)], food: Option<(usize, usize)>) -> GameData {//蛇头在(5,9)向右,地图上只有给出的墙和食物
+        let mut data = test_game(builtin_items());
+        data.stage = Level { spawn: (5, 9, Direction::Right), ..Level::blank() };
+        for (x, y) in walls.iter() {
+            data.stage.cells[*x][*y] = ItemType::Wall;
+        }
+        load_stage(&mut data);
+        if let Some((x, y)) = food {
+            data.map[x][y] = ItemType::Item(0);
+        }
+        data
+    }
+
+    #[test]
+    fn parses_agent_names() {
+        for kind in AGENTS.iter() {
+            assert_eq!(AgentKind::parse(kind.key()), Some(*kind));
+        }
+        assert_eq!(AgentKind::parse("dijkstra"), None);
+    }
+
+    #[test]
+    fn agents_avoid_obstacles() {
+        let data = board(&[(6, 9), (5, 8)], Some((5, 3)));
+        for kind in AGENTS.iter() {
+            assert_eq!(kind.agent().decide(&GameView::new(&data, 0)), Some(Direction::Down), "{}", kind.key());
+        }
+    }
+
+    #[test]
+    fn searches_find_the_way_around_walls() {
+        let data = board(&[], Some((5, 3)));
+        assert_eq!(AgentKind::Greedy.agent().decide(&GameView::new(&data, 0)), Some(Direction::Up));
+        let walls: Vec<(usize, usize)> = (2..7).map(|x| (x, 8)).collect(); //食物在墙的另一边,从右边绕过去更近
+        let data = board(&walls, Some((5, 3)));
+        for kind in [AgentKind::Bfs, AgentKind::AStar].iter() {
+            assert_eq!(kind.agent().decide(&GameView::new(&data, 0)), Some(Direction::Right));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use ncursesw::*;
+use rand::Rng;
+
+use crate::ai::AgentKind;
+use crate::level::Level;
+use crate::rules::{GameMode, Variant};
+use crate::watch::{self, Publisher};
+use crate::{addstr, draw_board, draw_frame, load_stage, move_cursor, reached_goal, set_color, set_cursor_visiable, step, timestamp, update_data};
+use crate::{Controller, GameData};
+
+pub const IDLE_SECONDS: i64 = 30; //选关界面空闲这么多秒后进入演示
+
+pub fn run_attract(data: &mut GameData) {//电脑自己玩一局作为演示,按任意键返回选关界面
+    let mode = data.mode;
+    let bots = data.bots;
+    let level = data.level;
+    let stage = data.stage.clone();
+    let start_hp = data.start_hp;
+    let variant = data.rules.variant;
+    data.mode = GameMode::Single;
+    data.rules.variant = Variant::Classic; //演示使用经典玩法
+    data.bots = 1;
+    data.start_hp = 6;
+    set_cursor_visiable(false);
+    draw_frame(data);
+    start_demo(data);
+    cbreak().unwrap();
+    loop {
+        data.t1 = timestamp() - data.t2; //刷新游戏运行时间
+        update_data(data);
+        draw_board(data);
+        move_cursor(21, 11);
+        set_color(data.colors.white);//调成白色
+        addstr(if data.tick % 10 < 7 { "PRESS ANY KEY" } else { "             " }); //闪烁的提示
+        refresh().unwrap();
+        timeout(Duration::from_millis(data.velocity as u64)).unwrap(); //等待按键的同时控制蛇的移动速度
+        let key = getch();
+        nodelay(stdscr(), false).unwrap();
+        if key.is_ok() {
+            break;
+        }
+        step(data);
+        watch::publish(data, Publisher::frame); //观众也能看到演示
+        if !data.snakes[0].alive || reached_goal(data, &data.snakes[0]) { //演示结束后换一关重新开始
+            start_demo(data);
+        }
+    }
+    watch::publish(data, |publisher, _| publisher.menu());
+    flushinp().unwrap();
+    data.mode = mode;
+    data.bots = bots;
+    data.level = level;
+    data.stage = stage;
+    data.start_hp = start_hp;
+    data.rules.variant = variant;
+    draw_frame(data);
+    set_cursor_visiable(true);//显示光标
+}
+
+fn start_demo(data: &mut GameData) {//随机选一关,玩家的蛇也交给电脑控制
+    data.level = rand::thread_rng().gen_range(2, 6);
+    data.stage = Level::builtin(data.level);
+    data.seed = timestamp() as u64;
+    load_stage(data);
+    data.snakes[0].controller = Controller::Agent(AgentKind::AStar);
+    data.t2 = timestamp();
+    watch::publish(data, Publisher::restart);
+}
@@ -0,0 +1,158 @@
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::raw::{c_char, c_int, c_void};
+use std::os::unix::io::FromRawFd;
+use std::process::{Command, Stdio};
+use std::ptr;
+use std::thread;
+use std::time::Instant;
+
+use crate::json::Json;
+use crate::replay::{Replay, AUTOPLAY_SPEED};
+use crate::timestamp;
+
+const USAGE: &str = "用法：snake-rust cast 回放文件 输出文件.cast";
+const CAST_SIZE: (u16, u16) = (80, 25); //转换回放时使用的终端大小,正好放下整个界面
+
+#[repr(C)]
+struct WinSize {
+    ws_row: u16,
+    ws_col: u16,
+    ws_xpixel: u16,
+    ws_ypixel: u16
+}
+
+#[link(name = "util")]
+extern "C" {
+    fn forkpty(master: *mut c_int, name: *mut c_char, termp: *const c_void, winp: *const WinSize) -> c_int;
+}
+
+#[link(name = "c")]
+extern "C" {
+    fn execv(path: *const c_char, argv: *const *const c_char) -> c_int;
+    fn waitpid(pid: c_int, status: *mut c_int, options: c_int) -> c_int;
+    fn _exit(status: c_int) -> !;
+}
+
+fn stty(args: &[&str]) -> Option<String> {//设置或读取当前终端的模式
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).stderr(Stdio::null()).output().ok()?;
+    if output.status.success() { Some(String::from_utf8_lossy(&output.stdout).trim().to_string()) } else { None }
+}
+
+fn terminal_size() -> (u16, u16) {//(列数, 行数),不是终端时使用默认大小
+    stty(&["size"]).and_then(|size| {
+        let mut parts = size.split_whitespace().map(|n| n.parse::<u16>().ok());
+        match (parts.next(), parts.next()) {
+            (Some(Some(rows)), Some(Some(columns))) => Some((columns, rows)),
+            _ => None
+        }
+    }).unwrap_or(CAST_SIZE)
+}
+
+pub fn run_record(path: &str, args: &[String]) -> Result<(), String> {//在伪终端中运行游戏,同时显示并录制所有输出
+    let size = terminal_size();
+    let saved = stty(&["-g"]);
+    if saved.is_some() {
+        stty(&["raw", "-echo"]); //按键原样转发给游戏
+    }
+    let result = capture(path, args, size, true, 1.0);
+    if let Some(saved) = saved {
+        stty(&[&saved]);
+    }
+    result
+}
+
+pub fn run_convert(args: &[String]) -> Result<(), String> {//把回放文件转换成asciicast,不需要终端
+    if args.len() != 2 {
+        return Err(String::from(USAGE));
+    }
+    Replay::load(&args[0])?; //先检查回放文件,出错时不创建输出文件
+    let command = vec![String::from("replay"), String::from("--autoplay"), args[0].clone()];
+    capture(&args[1], &command, CAST_SIZE, false, AUTOPLAY_SPEED)
+}
+
+fn capture(path: &str, args: &[String], size: (u16, u16), echo: bool, scale: f64) -> Result<(), String> {//scale:回放加速播放时把时间还原成原来的速度
+    let exe = std::env::current_exe().map_err(|e| format!("找不到程序自身：{}", e))?;
+    let mut argv = vec![CString::new(exe.to_string_lossy().into_owned()).map_err(|e| e.to_string())?];
+    for arg in args {
+        argv.push(CString::new(arg.as_str()).map_err(|e| e.to_string())?);
+    }
+    let mut pointers: Vec<*const c_char> = argv.iter().map(|arg| arg.as_ptr()).collect();
+    pointers.push(ptr::null());
+    let mut file = File::create(path).map_err(|e| format!("无法创建{}：{}", path, e))?;
+    let term = std::env::var("TERM").unwrap_or_else(|_| String::from("xterm-256color"));
+    std::env::set_var("TERM", &term); //没有终端时也让ncurses能输出
+    let header = Json::Object(vec![
+        (String::from("version"), Json::Number(2.0)),
+        (String::from("width"), Json::Number(size.0 as f64)),
+        (String::from("height"), Json::Number(size.1 as f64)),
+        (String::from("timestamp"), Json::Number((timestamp() / 1000) as f64)),
+        (String::from("env"), Json::Object(vec![(String::from("TERM"), Json::String(term))]))
+    ]);
+    writeln!(file, "{}", header).map_err(|e| format!("写入{}失败：{}", path, e))?;
+    let window = WinSize { ws_row: size.1, ws_col: size.0, ws_xpixel: 0, ws_ypixel: 0 };
+    let mut master: c_int = -1;
+    let pid = unsafe { forkpty(&mut master, ptr::null_mut(), ptr::null(), &window) };
+    if pid < 0 {
+        return Err(String::from("无法创建伪终端"));
+    }
+    if pid == 0 {
+        unsafe {
+            execv(pointers[0], pointers.as_ptr());
+            _exit(127);
+        }
+    }
+    let mut terminal = unsafe { File::from_raw_fd(master) };
+    if echo {
+        let mut input = terminal.try_clone().map_err(|e| e.to_string())?;
+        thread::spawn(move || {//把按键转发给游戏,游戏结束时随进程退出
+            let mut buffer = [0; 64];
+            let stdin = io::stdin();
+            let mut stdin = stdin.lock();
+            while let Ok(n) = stdin.read(&mut buffer) {
+                if n == 0 || input.write_all(&buffer[..n]).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    let start = Instant::now();
+    let stdout = io::stdout();
+    let mut pending = Vec::new(); //还没有凑成完整UTF-8字符的字节
+    let mut buffer = [0; 4096];
+    loop {
+        let n = match terminal.read(&mut buffer) {
+            Ok(0) | Err(_) => break, //游戏退出后读取伪终端会出错
+            Ok(n) => n
+        };
+        if echo {
+            let mut out = stdout.lock();
+            let _ = out.write_all(&buffer[..n]).and_then(|_| out.flush());
+        }
+        pending.extend_from_slice(&buffer[..n]);
+        let text = take_text(&mut pending);
+        if text.is_empty() {
+            continue;
+        }
+        let time = (start.elapsed().as_secs_f64() * scale * 1_000_000.0).round() / 1_000_000.0;
+        let event = Json::Array(vec![Json::Number(time), Json::String(String::from("o")), Json::String(text)]);
+        writeln!(file, "{}", event).map_err(|e| format!("写入{}失败：{}", path, e))?;
+    }
+    let mut status = 0;
+    unsafe {
+        waitpid(pid, &mut status, 0);
+    }
+    Ok(())
+}
+
+fn take_text(pending: &mut Vec<u8>) -> String {//取出完整的字符,结尾不完整的字节留到下一次
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(), //字符被截断
+        Err(_) => pending.len() //不是UTF-8,按替换字符输出
+    };
+    let text = String::from_utf8_lossy(&pending[..valid]).into_owned();
+    pending.drain(..valid);
+    text
+}
@@ -0,0 +1,382 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ncursesw::*;
+use rand::Rng;
+
+use crate::ai::AgentKind;
+use crate::level::{Level, MAP_HEIGHT, MAP_WIDTH};
+use crate::rules::{GameMode, Ruleset, Variant, WallRule};
+use crate::{addstr, clear_screen, fit, move_cursor, seed_rng, set_color, set_cursor_visiable, team_length, GameData, ItemType};
+
+const HISTORY_DAYS: i64 = 14; //挑战界面显示最近多少天的记录
+const TOP_RESULTS: usize = 5; //挑战界面显示的最好成绩数
+
+pub struct Challenge {//某一天的每日挑战,同一天在任何电脑上得到的种子、规则和地图都相同
+    pub day: i64, //从1970-01-01起的天数(UTC)
+    pub date: String, //例如2026-10-19
+    pub seed: u64,
+    pub rules: Ruleset,
+    pub stage: Level
+}
+
+#[derive(Clone)]
+pub struct DailyResult {
+    pub day: i64,
+    pub date: String,
+    pub score: i32,
+    pub length: i16,
+    pub seconds: i64
+}
+
+pub struct DailyBoard {//本机每天唯一一次计分挑战的成绩
+    path: PathBuf, //成绩文件
+    pub results: Vec<DailyResult>
+}
+
+pub struct DailyRun {//正在进行的每日挑战,结束后恢复选关界面的设置
+    pub challenge: Challenge,
+    pub scored: bool, //今天的第一次挑战才计分,之后只是练习
+    mode: GameMode,
+    rules: Ruleset,
+    bots: usize,
+    autopilot: Option<AgentKind>
+}
+
+fn today() -> i64 {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    (seconds / 86400) as i64
+}
+
+fn civil_date(day: i64) -> String {//天数换成公历日期
+    let z = day + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    format!("{:04}-{:02}-{:02}", yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m, d)
+}
+
+fn date_seed(date: &str) -> u64 {//FNV-1a,不依赖标准库哈希的实现,保证各个版本得到相同的种子
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in format!("snake-rust daily {}", date).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+fn near_spawn(x: usize, y: usize) -> bool {//出生点和蛇头前方留出空地
+    let (sx, sy, _) = Level::blank().spawn;
+    y + 1 >= sy && y <= sy + 1 && (x <= sx + 8 || x + 2 >= MAP_WIDTH) //蛇尾会绕到地图另一侧
+}
+
+impl Challenge {
+    pub fn today() -> Challenge {
+        Challenge::for_day(today())
+    }
+
+    pub fn for_day(day: i64) -> Challenge {//由日期决定种子,再由种子决定规则和地图
+        let date = civil_date(day);
+        let seed = date_seed(&date);
+        let mut rng = seed_rng(seed);
+        let variants = [Variant::Classic, Variant::TimeAttack(60), Variant::TimeAttack(120), Variant::Survival];
+        let walls = [WallRule::WrapPenalty, WallRule::Solid, WallRule::FreeWrap];
+        let speeds = [200, 150, 120, 100];
+        let rules = Ruleset { wall: walls[rng.gen_range(0, walls.len())], variant: variants[rng.gen_range(0, variants.len())] };
+        let mut stage = Level {
+            name: String::from("每日挑战"),
+            velocity: speeds[rng.gen_range(0, speeds.len())],
+            goal_length: rng.gen_range(15, 31),
+            ..Level::blank()
+        };
+        for _ in 0..rng.gen_range(4, 9) { //随机放几段横的或竖的墙
+            let (mut x, mut y) = (rng.gen_range(0, MAP_WIDTH), rng.gen_range(0, MAP_HEIGHT));
+            let horizontal = rng.gen_range(0, 2) == 0;
+            for _ in 0..rng.gen_range(3, 9) {
+                if x >= MAP_WIDTH || y >= MAP_HEIGHT {
+                    break;
+                }
+                if !near_spawn(x, y) {
+                    stage.cells[x][y] = ItemType::Wall;
+                }
+                if horizontal { x += 1 } else { y += 1 }
+            }
+        }
+        Challenge { day: day, date: date, seed: seed, rules: rules, stage: stage }
+    }
+}
+
+impl DailyBoard {
+    pub fn load(path: PathBuf) -> DailyBoard {//文件不存在或无法解析的行会被忽略
+        let mut board = DailyBoard { path: path, results: Vec::new() };
+        let text = fs::read_to_string(&board.path).unwrap_or_default();
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 5 {
+                continue;
+            }
+            if let Ok(day) = fields[0].parse() {
+                board.results.push(DailyResult {
+                    day: day,
+                    date: fields[1].to_string(),
+                    score: fields[2].parse().unwrap_or(0),
+                    length: fields[3].parse().unwrap_or(0),
+                    seconds: fields[4].parse().unwrap_or(0)
+                });
+            }
+        }
+        board
+    }
+
+    pub fn played(&self, day: i64) -> Option<&DailyResult> {
+        self.results.iter().find(|result| result.day == day)
+    }
+
+    pub fn record(&mut self, result: DailyResult) -> Result<(), String> {//同一天只保留一条成绩
+        self.results.retain(|old| old.day != result.day);
+        self.results.push(result);
+        self.results.sort_by_key(|result| result.day);
+        let mut text = String::new();
+        for result in self.results.iter() {
+            text += &format!("{}\t{}\t{}\t{}\t{}\n", result.day, result.date, result.score, result.length, result.seconds);
+        }
+        fs::write(&self.path, text).map_err(|e| format!("无法保存每日挑战：{}", e))
+    }
+
+    pub fn streak(&self, day: i64) -> (u32, u32) {//(当前连续挑战的天数, 最长连续的天数),今天还没挑战时从昨天算起
+        let mut current = 0;
+        let mut expected = if self.played(day).is_some() { day } else { day - 1 };
+        for result in self.results.iter().rev() {
+            if result.day == expected {
+                current += 1;
+                expected -= 1;
+            } else if result.day < expected {
+                break;
+            }
+        }
+        let (mut best, mut run, mut last) = (0, 0, i64::MIN);
+        for result in self.results.iter() {
+            run = if result.day == last + 1 { run + 1 } else { 1 };
+            last = result.day;
+            best = best.max(run);
+        }
+        (current, best)
+    }
+
+    pub fn best(&self) -> Vec<&DailyResult> {
+        let mut results: Vec<&DailyResult> = self.results.iter().collect();
+        results.sort_by(|a, b| b.score.cmp(&a.score).then(b.day.cmp(&a.day)));
+        results.truncate(TOP_RESULTS);
+        results
+    }
+}
+
+impl DailyRun {
+    pub fn restore(self, data: &mut GameData) {//把选关界面的设置改回来
+        data.mode = self.mode;
+        data.rules = self.rules;
+        data.bots = self.bots;
+        data.autopilot = self.autopilot;
+    }
+}
+
+fn draw_board(data: &GameData, today: i64, row: i32) {//从row行开始输出连续天数、最近几天和最好成绩
+    let board = &data.daily_board;
+    let (current, best) = board.streak(today);
+    set_color(data.colors.white);//调成白色
+    move_cursor(4, row);
+    addstr(&fit(&format!("连续挑战：{}天  最长连续：{}天", current, best), 48));
+    move_cursor(4, row + 1);
+    addstr(&format!("最近{}天：", HISTORY_DAYS));
+    for i in 0..HISTORY_DAYS {
+        let day = today - HISTORY_DAYS + 1 + i;
+        set_color(if board.played(day).is_some() { data.colors.green } else { data.colors.red });
+        addstr(if board.played(day).is_some() { "■" } else { "□" });
+    }
+    set_color(data.colors.white);//调成白色
+    move_cursor(4, row + 3);
+    addstr("最好成绩：");
+    let results = board.best();
+    for (i, result) in results.iter().enumerate() {
+        move_cursor(6, row + 4 + i as i32);
+        addstr(&fit(&format!("{}{:>7}分  长度{:<4}用时{}秒", result.date, result.score, result.length, result.seconds), 46));
+    }
+    if results.is_empty() {
+        move_cursor(6, row + 4);
+        addstr("无");
+    }
+}
+
+pub fn run_menu(data: &mut GameData) -> bool {//显示今天的挑战,返回true表示开始挑战
+    set_cursor_visiable(false);
+    let challenge = Challenge::today();
+    clear_screen();
+    move_cursor(4, 2);
+    set_color(data.colors.yellow);//调成黄色
+    addstr(&format!("每日挑战 {}", challenge.date));
+    set_color(data.colors.white);//调成白色
+    move_cursor(4, 4);
+    addstr(&fit(&format!("玩法：{}  撞墙规则：{}", challenge.rules.variant.name(), challenge.rules.wall.name()), 48));
+    move_cursor(4, 5);
+    addstr(&fit(&format!("速度：{}毫秒一步  目标长度：{}", challenge.stage.velocity, challenge.stage.goal_length), 48));
+    move_cursor(4, 7);
+    let scored = match data.daily_board.played(challenge.day) {
+        Some(result) => {
+            addstr(&fit(&format!("今天已挑战：{}分  长度{}  用时{}秒", result.score, result.length, result.seconds), 48));
+            false
+        },
+        None => {
+            addstr(&fit("今天还没有挑战,只有第一次挑战计分", 48));
+            true
+        }
+    };
+    draw_board(data, challenge.day, 9);
+    move_cursor(4, 21);
+    set_color(data.colors.yellow);//调成黄色
+    addstr(if scored { "Enter：开始挑战  Esc：返回" } else { "Enter：练习(不计分)  Esc：返回" });
+    refresh().unwrap();
+    cbreak().unwrap();
+    let start = loop {
+        match getch() {
+            Ok(CharacterResult::Character('\n')) | Ok(CharacterResult::Key(KeyBinding::Enter)) => break true,
+            Ok(CharacterResult::Character('\u{001B}')) | Ok(CharacterResult::Character('q')) | Ok(CharacterResult::Key(KeyBinding::Exit)) => break false,
+            _ => continue
+        }
+    };
+    clear_screen();
+    if !start {
+        return false;
+    }
+    if scored { //开始时就用掉今天的机会,中途退出也算一次
+        let result = DailyResult { day: challenge.day, date: challenge.date.clone(), score: 0, length: 0, seconds: 0 };
+        let _ = data.daily_board.record(result);
+    }
+    let run = DailyRun {
+        scored: scored,
+        mode: data.mode,
+        rules: data.rules.clone(),
+        bots: data.bots,
+        autopilot: data.autopilot,
+        challenge: challenge
+    };
+    data.mode = GameMode::Single; //所有人使用同样的设置
+    data.rules = run.challenge.rules.clone();
+    data.bots = 0;
+    data.autopilot = None;
+    data.start_hp = 6;
+    data.level = 0;
+    data.stage = run.challenge.stage.clone();
+    data.daily = Some(run);
+    true
+}
+
+pub fn record(data: &mut GameData) -> Result<(), String> {//计分的挑战结束时保存成绩,之后再来一局只是练习
+    let result = match data.daily.as_mut() {
+        Some(run) if run.scored => {
+            run.scored = false;
+            DailyResult {
+                day: run.challenge.day,
+                date: run.challenge.date.clone(),
+                score: data.stats[0].score,
+                length: team_length(data, 0),
+                seconds: data.t1 / 1000
+            }
+        },
+        _ => return Ok(())
+    };
+    data.daily_board.record(result)
+}
+
+pub fn finish(data: &mut GameData) {//结算后保存成绩并输出每日挑战的记录
+    let scored = data.daily.as_ref().map_or(false, |run| run.scored);
+    let saved = record(data);
+    let (day, date) = match data.daily.as_ref() {
+        Some(run) => (run.challenge.day, run.challenge.date.clone()),
+        None => return
+    };
+    clear_screen();
+    move_cursor(4, 2);
+    set_color(data.colors.yellow);//调成黄色
+    addstr(&format!("每日挑战 {}", date));
+    move_cursor(4, 4);
+    set_color(data.colors.white);//调成白色
+    addstr(&fit(&format!("本局得分：{}{}", data.stats[0].score, if scored { "" } else { "(练习,不计分)" }), 48));
+    if let Err(message) = saved {
+        move_cursor(4, 5);
+        set_color(data.colors.red);//调成红色
+        addstr(&fit(&message, 48));
+    }
+    draw_board(data, day, 7);
+    refresh().unwrap();
+    thread::sleep(Duration::from_secs(3));//延时
+    clear_screen();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(day: i64, score: i32) -> DailyResult {
+        DailyResult { day: day, date: civil_date(day), score: score, length: 10, seconds: 30 }
+    }
+
+    fn board(days: &[i64]) -> DailyBoard {
+        DailyBoard { path: PathBuf::new(), results: days.iter().map(|day| result(*day, 0)).collect() }
+    }
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(-1), "1969-12-31");
+        assert_eq!(civil_date(11016), "2000-02-29");
+        assert_eq!(civil_date(20745), "2026-10-19");
+        assert_eq!(civil_date(47541), "2100-03-01");
+    }
+
+    #[test]
+    fn counts_streaks() {
+        let board = board(&[1, 2, 3, 5, 6]);
+        assert_eq!(board.streak(6), (2, 3)); //今天已经挑战
+        assert_eq!(board.streak(7), (2, 3)); //今天还没挑战,从昨天算起
+        assert_eq!(board.streak(8), (0, 3)); //断了一天
+        assert_eq!(board.streak(5), (1, 3));
+        assert_eq!(DailyBoard { path: PathBuf::new(), results: Vec::new() }.streak(6), (0, 0));
+    }
+
+    #[test]
+    fn saves_one_result_per_day() {
+        let path = std::env::temp_dir().join(format!("snake-rust-daily-{}.txt", std::process::id()));
+        let mut board = DailyBoard::load(path.clone());
+        board.record(result(20, 50)).unwrap();
+        board.record(result(18, 70)).unwrap();
+        board.record(result(20, 90)).unwrap();
+        let loaded = DailyBoard::load(path.clone());
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.results.iter().map(|result| (result.day, result.score)).collect::<Vec<_>>(), vec![(18, 70), (20, 90)]);
+        assert_eq!(loaded.played(18).map(|result| result.date.as_str()), Some("1970-01-19"));
+        assert_eq!(loaded.best()[0].day, 20);
+    }
+
+    #[test]
+    fn challenges_depend_only_on_the_day() {
+        let (first, second) = (Challenge::for_day(20745), Challenge::for_day(20745));
+        assert_eq!(first.date, "2026-10-19");
+        assert_eq!(first.seed, second.seed);
+        assert_eq!((first.rules.wall, first.rules.variant), (second.rules.wall, second.rules.variant));
+        assert_eq!(first.stage.to_text(), second.stage.to_text());
+        assert_ne!(Challenge::for_day(20746).seed, first.seed);
+        for day in 20000..20100 {
+            let challenge = Challenge::for_day(day);
+            let (x, y, _) = challenge.stage.spawn;
+            for dx in 0..8 { //出生点前方没有墙
+                assert_eq!(challenge.stage.cells[x + dx][y], ItemType::None);
+            }
+        }
+    }
+}
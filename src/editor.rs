@@ -0,0 +1,234 @@
+use ncursesw::*;
+
+use crate::items::BARRIER;
+use crate::level::{spawn_char, Level, MAP_HEIGHT, MAP_WIDTH, MAX_VELOCITY, MIN_VELOCITY};
+use crate::rules::WallRule;
+use crate::{addstr, begin_game, draw_frame, draw_item, draw_map, fit, load_stage, move_cursor, read_line, set_color, set_cursor_visiable, start_stage};
+use crate::{Direction, GameData, ItemType};
+
+struct Editor {
+    level: Level, //正在编辑的地图
+    path: String, //地图文件的路径
+    x: usize, //光标所在格子的横坐标
+    y: usize, //光标所在格子的纵坐标
+    brush: ItemType, //当前画笔
+    pen_down: bool, //移动光标时是否连续绘制
+    message: String //底部的提示信息
+}
+
+pub fn run_editor(data: &mut GameData, path: &str) {//地图编辑器
+    let mut editor = Editor {
+        level: Level::load(path).unwrap_or_else(|_| Level::blank()),
+        path: path.to_string(),
+        x: 0,
+        y: 0,
+        brush: ItemType::Wall,
+        pen_down: false,
+        message: String::new()
+    };
+    cbreak().unwrap();
+    redraw(data, &editor);
+    loop {
+        draw_status(data, &editor);
+        let key = match getch() {
+            Ok(key) => key,
+            Err(_) => continue
+        };
+        match key {
+            CharacterResult::Key(KeyBinding::UpArrow) => editor.y = (editor.y + MAP_HEIGHT - 1) % MAP_HEIGHT,
+            CharacterResult::Key(KeyBinding::DownArrow) => editor.y = (editor.y + 1) % MAP_HEIGHT,
+            CharacterResult::Key(KeyBinding::LeftArrow) => editor.x = (editor.x + MAP_WIDTH - 1) % MAP_WIDTH,
+            CharacterResult::Key(KeyBinding::RightArrow) => editor.x = (editor.x + 1) % MAP_WIDTH,
+            CharacterResult::Character('\n') | CharacterResult::Key(KeyBinding::Enter) => editor.pen_down = !editor.pen_down,
+            CharacterResult::Character('w') => paint(data, &mut editor, ItemType::Wall),
+            CharacterResult::Character('b') => paint(data, &mut editor, ItemType::Item(BARRIER)),
+            CharacterResult::Character(' ') => paint(data, &mut editor, ItemType::None),
+            CharacterResult::Character('s') => { //设置出生点，在出生点上再按则旋转方向
+                let (x, y, direction) = editor.level.spawn;
+                let second = editor.level.second_spawn();
+                if editor.level.spawn2.is_some() && (second.0, second.1) == (editor.x, editor.y) {
+                    editor.message = String::from("玩家1的出生点不能和玩家2的重合");
+                    continue;
+                }
+                let direction = if (x, y) == (editor.x, editor.y) { rotate(direction) } else { direction };
+                editor.level.cells[editor.x][editor.y] = ItemType::None;
+                editor.level.spawn = (editor.x, editor.y, direction);
+                draw_editor_cell(data, &editor, x, y);
+                draw_editor_cell(data, &editor, editor.x, editor.y);
+            },
+            CharacterResult::Character('2') => { //设置玩家2的出生点，在出生点上再按则旋转方向，在玩家1出生点上按则恢复对称
+                let (x, y, direction) = editor.level.second_spawn();
+                if (editor.x, editor.y) == (editor.level.spawn.0, editor.level.spawn.1) {
+                    editor.level.spawn2 = None;
+                } else {
+                    let direction = if (x, y) == (editor.x, editor.y) { rotate(direction) } else { direction };
+                    editor.level.cells[editor.x][editor.y] = ItemType::None;
+                    editor.level.spawn2 = Some((editor.x, editor.y, direction));
+                }
+                draw_editor_cell(data, &editor, x, y);
+                draw_editor_cell(data, &editor, editor.x, editor.y);
+            },
+            CharacterResult::Character('n') => {
+                let name = read_line(2, 24, "地图名称：");
+                if !name.is_empty() {
+                    editor.level.name = name;
+                }
+            },
+            CharacterResult::Character('v') => {
+                if let Ok(velocity) = read_line(2, 24, &format!("移动间隔({}-{}毫秒)：", MIN_VELOCITY, MAX_VELOCITY)).parse::<i32>() {
+                    editor.level.velocity = velocity.max(MIN_VELOCITY).min(MAX_VELOCITY);
+                }
+            },
+            CharacterResult::Character('l') => {
+                if let Ok(goal) = read_line(2, 24, "目标长度(5-81)：").parse::<i16>() {
+                    editor.level.goal_length = goal.max(5).min(81);
+                }
+            },
+            CharacterResult::Character('f') => {
+                if let Ok(goal) = read_line(2, 24, "目标食物(0为不限)：").parse::<i16>() {
+                    editor.level.goal_food = goal.max(0);
+                }
+            },
+            CharacterResult::Character('r') => { //切换本关的撞墙规则
+                editor.level.wall = match editor.level.wall {
+                    None => Some(WallRule::WrapPenalty),
+                    Some(WallRule::FreeWrap) => None,
+                    Some(wall) => Some(wall.next())
+                };
+            },
+            CharacterResult::Character('o') => {
+                editor.message = match editor.level.save(&editor.path) {
+                    Ok(_) => format!("已保存到{}", editor.path),
+                    Err(message) => message
+                };
+            },
+            CharacterResult::Character('t') => { //用当前地图试玩
+                data.stage = editor.level.clone();
+                data.stage.number = 0;
+                data.level = 0;
+                data.start_hp = 6;
+                load_stage(data);
+                draw_frame(data);
+                draw_map(data);
+                data.test_play = true;
+                while begin_game(data) { //结算界面选择再来一局
+                    start_stage(data);
+                    set_cursor_visiable(false);
+                }
+                data.test_play = false;
+                cbreak().unwrap();
+                redraw(data, &editor);
+            },
+            CharacterResult::Character('\u{001B}') | CharacterResult::Key(KeyBinding::Exit) => break, //Esc键退出编辑器
+            _ => continue
+        }
+        if editor.pen_down {
+            let brush = editor.brush;
+            paint(data, &mut editor, brush);
+        }
+    }
+    set_cursor_visiable(false);
+}
+
+fn rotate(direction: Direction) -> Direction {//顺时针旋转出生方向
+    match direction {
+        Direction::Right => Direction::Down,
+        Direction::Down => Direction::Left,
+        Direction::Left => Direction::Up,
+        Direction::Up => Direction::Right
+    }
+}
+
+fn paint(data: &GameData, editor: &mut Editor, item: ItemType) {//用画笔绘制光标所在的格子
+    editor.brush = item;
+    let (x, y, _) = editor.level.spawn;
+    let second = editor.level.spawn2.map(|(x, y, _)| (x, y));
+    if (x, y) != (editor.x, editor.y) && second != Some((editor.x, editor.y)) { //出生点不能被覆盖
+        editor.level.cells[editor.x][editor.y] = item;
+        draw_editor_cell(data, editor, editor.x, editor.y);
+    }
+}
+
+fn draw_editor_cell(data: &GameData, editor: &Editor, x: usize, y: usize) {//用游戏中的字符输出一格
+    move_cursor(2 * (x as i32 + 1), y as i32 + 1);
+    if (x, y) == (editor.level.spawn.0, editor.level.spawn.1) {
+        set_color(data.colors.red);
+        addstr("◆");
+        return;
+    }
+    if editor.level.spawn2.map(|(x, y, _)| (x, y)) == Some((x, y)) {
+        set_color(data.colors.green);
+        addstr("◆");
+        return;
+    }
+    draw_item(data, editor.level.cells[x][y]);
+}
+
+fn redraw(data: &mut GameData, editor: &Editor) {//重新绘制整个编辑器界面
+    draw_frame(data);
+    for y in 1..23 { //清除游戏中的提示文字
+        if y != 11 {
+            move_cursor(56, y);
+            addstr(&fit("", 22));
+        }
+    }
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            draw_editor_cell(data, editor, x, y);
+        }
+    }
+    set_color(data.colors.yellow);
+    move_cursor(19, 0);
+    addstr("| | |地图编辑器| | |");
+    move_cursor(56, 12);
+    addstr("方向键：移动光标");
+    move_cursor(56, 13);
+    addstr("w：墙    b：障碍物");
+    move_cursor(56, 14);
+    addstr("s：出生点(再按旋转)");
+    move_cursor(56, 15);
+    addstr("空格：擦除 2：玩家2");
+    move_cursor(56, 16);
+    addstr("确定键：落笔/抬笔");
+    move_cursor(56, 17);
+    addstr("n：名称  v：速度");
+    move_cursor(56, 18);
+    addstr("l：目标长度");
+    move_cursor(56, 19);
+    addstr("f：目标食物 r：撞墙");
+    move_cursor(56, 20);
+    addstr("t：试玩  o：保存");
+    move_cursor(56, 21);
+    addstr("Esc：退出编辑器");
+}
+
+fn draw_status(data: &GameData, editor: &Editor) {//输出地图设置和光标位置
+    let brush = match editor.brush {
+        ItemType::Wall => "墙",
+        ItemType::Item(_) => "障碍物",
+        _ => "擦除"
+    };
+    let lines = [
+        format!("名称：{}", editor.level.name),
+        format!("速度：{}毫秒", editor.level.velocity),
+        format!("目标长度：{}", editor.level.goal_length),
+        format!("目标食物：{}", if editor.level.goal_food == 0 { String::from("不限") } else { editor.level.goal_food.to_string() }),
+        format!("撞墙：{}", editor.level.wall.map_or("默认", |wall| wall.name())),
+        format!("画笔：{}{}", brush, if editor.pen_down { "(落笔)" } else { "" }),
+        format!("出生点：({}, {}) {}", editor.level.spawn.0, editor.level.spawn.1, spawn_char(editor.level.spawn.2)),
+        match editor.level.spawn2 {
+            Some((x, y, direction)) => format!("玩家2：({}, {}) {}", x, y, spawn_char(direction)),
+            None => String::from("玩家2：对称")
+        }
+    ];
+    for i in 0..lines.len() {
+        move_cursor(56, 2 + i as i32);
+        set_color(data.colors.yellow);
+        addstr(&fit(&lines[i], 22));
+    }
+    move_cursor(2, 24);
+    addstr(&fit(&editor.message, 76));
+    move_cursor(2 * (editor.x as i32 + 1), editor.y as i32 + 1);
+    set_cursor_visiable(true);
+    refresh().unwrap();
+}
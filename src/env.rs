@@ -0,0 +1,261 @@
+use std::io::{self, BufRead, Write};
+
+use crate::items::ItemKind;
+use crate::json::{self, Json};
+use crate::simulate::{self, Options};
+use crate::sync::{direction_name, parse_direction};
+use crate::{cause_name, reached_goal, snake_cell, step, Direction, GameData, ItemType};
+
+const USAGE: &str = "用法：snake-rust env [--level 1-6 | --map 地图文件] [--wall wrap|solid|free] [--bots 数量] [--bot-agent 策略]
+                [--hp 生命值] [--max-ticks 步数] [--reward-food 奖励] [--reward-damage 奖励] [--reward-death 奖励]
+                [--reward-win 奖励] [--reward-step 奖励]
+每行一个请求：{\"cmd\":\"reset\",\"seed\":1}、{\"cmd\":\"step\",\"action\":\"up\"}、{\"cmd\":\"close\"}";
+
+const MAX_NUMBER_SEED: u64 = 1 << 53; //JSON数字能精确表示的最大整数
+
+#[derive(Clone)]
+struct Rewards {
+    food: f64, //每吃到一个食物
+    damage: f64, //每扣一点生命值
+    death: f64, //死亡
+    win: f64, //达到关卡目标
+    step: f64 //每走一步
+}
+
+impl Rewards {
+    fn set(&mut self, key: &str, value: f64) -> Result<(), String> {
+        match key {
+            "food" => self.food = value,
+            "damage" => self.damage = value,
+            "death" => self.death = value,
+            "win" => self.win = value,
+            "step" => self.step = value,
+            _ => return Err(format!("未知的奖励：{}", key))
+        }
+        Ok(())
+    }
+}
+
+struct Env {
+    data: GameData,
+    options: Options,
+    rewards: Rewards, //命令行设置的奖励
+    current: Rewards, //这一局使用的奖励,reset时可以修改
+    next_seed: u64, //reset没有指定种子时使用
+    done: bool //这一局是否已经结束
+}
+
+pub fn run_env(args: &[String], items: Vec<ItemKind>) -> Result<(), String> {//用一行一个JSON的协议把游戏提供给强化学习程序
+    let mut rewards = Rewards { food: 1.0, damage: -0.5, death: -1.0, win: 5.0, step: 0.0 };
+    let mut rest = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if args[i].starts_with("--reward-") {
+            let value = args.get(i + 1).and_then(|value| value.parse::<f64>().ok())
+                .ok_or(format!("{}必须是数字\n{}", args[i], USAGE))?;
+            rewards.set(&args[i]["--reward-".len()..], value).map_err(|message| format!("{}\n{}", message, USAGE))?;
+            i += 2;
+        } else {
+            rest.push(args[i].clone());
+            i += 1;
+        }
+    }
+    let options = simulate::parse_options(&rest).map_err(|message| format!("{}\n{}", message, USAGE))?;
+    let mut env = Env {
+        data: simulate::headless_game(items, &options),
+        next_seed: options.seed,
+        options: options,
+        current: rewards.clone(),
+        rewards: rewards,
+        done: true
+    };
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|e| format!("读取请求失败：{}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match json::parse(&line) {
+            Ok(request) => {
+                if request.get("cmd").and_then(Json::as_str) == Some("close") {
+                    break;
+                }
+                env.handle(&request).unwrap_or_else(|message| Json::Object(vec![(String::from("error"), Json::String(message))]))
+            },
+            Err(message) => Json::Object(vec![(String::from("error"), Json::String(message))])
+        };
+        writeln!(out, "{}", reply).and_then(|_| out.flush()).map_err(|e| format!("输出失败：{}", e))?;
+    }
+    Ok(())
+}
+
+impl Env {
+    fn handle(&mut self, request: &Json) -> Result<Json, String> {
+        match request.get("cmd").and_then(Json::as_str) {
+            Some("reset") => self.reset(request),
+            Some("step") => self.step(request.get("action").unwrap_or(&Json::Null)),
+            Some(cmd) => Err(format!("未知的命令：{}", cmd)),
+            None => Err(String::from("请求中缺少cmd"))
+        }
+    }
+
+    fn reset(&mut self, request: &Json) -> Result<Json, String> {//开始新的一局,返回观察
+        let seed = match request.get("seed") {
+            Some(Json::Number(n)) if *n >= 0.0 && n.fract() == 0.0 && *n <= MAX_NUMBER_SEED as f64 => *n as u64,
+            Some(Json::String(text)) => text.parse().map_err(|_| format!("seed必须是非负整数：{}", text))?,
+            Some(Json::Null) | None => self.next_seed,
+            Some(_) => return Err(format!("seed必须是不超过{}的非负整数,更大的种子请写成字符串", MAX_NUMBER_SEED))
+        };
+        self.current = self.rewards.clone();
+        if let Some(Json::Object(fields)) = request.get("rewards") { //这一局单独设置的奖励
+            for (key, value) in fields {
+                self.current.set(key, value.as_f64().ok_or(format!("奖励{}必须是数字", key))?)?;
+            }
+        }
+        self.next_seed = seed.wrapping_add(1); //最大的种子之后回到0
+        simulate::start(&mut self.data, &self.options, seed);
+        self.done = false;
+        Ok(Json::Object(vec![
+            (String::from("observation"), observation(&self.data)),
+            (String::from("info"), self.info())
+        ]))
+    }
+
+    fn step(&mut self, action: &Json) -> Result<Json, String> {//走一步,返回观察、奖励、是否结束和其他信息
+        if self.done {
+            return Err(String::from("这一局已经结束,请先reset"));
+        }
+        let direction = match action {
+            Json::Null => None,
+            Json::String(name) => Some(parse_direction(name).ok_or(format!("未知的动作：{}", name))?),
+            Json::Number(n) if n.fract() != 0.0 => return Err(format!("动作编号必须是0-3：{}", n)), //不能把0.5当成0
+            Json::Number(n) => Some(match *n as i64 {
+                0 => Direction::Up,
+                1 => Direction::Down,
+                2 => Direction::Left,
+                3 => Direction::Right,
+                _ => return Err(format!("动作编号必须是0-3：{}", n))
+            }),
+            _ => return Err(String::from("动作必须是方向名、0-3或null"))
+        };
+        let food = self.data.stats[0].food;
+        let hp = self.data.stats[0].hp;
+        if let Some(direction) = direction {
+            self.data.snakes[0].turns.clear();
+            self.data.snakes[0].turns.push(direction);
+        }
+        step(&mut self.data);
+        let mut reward = self.current.step;
+        reward += (self.data.stats[0].food - food) as f64 * self.current.food;
+        reward += (hp - self.data.stats[0].hp).max(0) as f64 * -self.current.damage.abs();
+        let truncated = self.data.tick >= self.options.max_ticks;
+        if !self.data.snakes[0].alive {
+            reward += self.current.death;
+            self.done = true;
+        } else if reached_goal(&self.data, &self.data.snakes[0]) {
+            reward += self.current.win;
+            self.done = true;
+        } else if truncated {
+            self.done = true;
+        }
+        let mut info = self.info();
+        if let Json::Object(fields) = &mut info {
+            fields.push((String::from("truncated"), Json::Bool(truncated && self.data.snakes[0].alive)));
+        }
+        Ok(Json::Object(vec![
+            (String::from("observation"), observation(&self.data)),
+            (String::from("reward"), Json::Number(reward)),
+            (String::from("done"), Json::Bool(self.done)),
+            (String::from("info"), info)
+        ]))
+    }
+
+    fn info(&self) -> Json {
+        let data = &self.data;
+        let cause = match data.stats[0].last_hit {
+            Some(cause) if !data.snakes[0].alive => Json::String(cause_name(data, cause)),
+            _ => Json::Null
+        };
+        Json::Object(vec![
+            (String::from("seed"), Json::String(data.seed.to_string())), //写成字符串,超过2^53的种子也不会失去精度
+            (String::from("tick"), Json::Number(data.tick as f64)),
+            (String::from("score"), Json::Number(data.stats[0].score as f64)),
+            (String::from("cause"), cause)
+        ])
+    }
+}
+
+fn observation(data: &GameData) -> Json {//grid按行排列:0空地 1墙 2食物 3伤害物品 4其他物品 5自己的蛇身 6自己的蛇头 7其他蛇身 8其他蛇头
+    let mut grid = [[0u8; 26]; 22];
+    for x in 0..26 {
+        for y in 0..22 {
+            grid[y][x] = match data.map[x][y] {
+                ItemType::None => 0,
+                ItemType::Wall => 1,
+                ItemType::Item(kind) if data.items[kind].grows() => 2,
+                ItemType::Item(kind) if data.items[kind].damages() => 3,
+                ItemType::Item(_) => 4
+            };
+        }
+    }
+    for (i, snake) in data.snakes.iter().enumerate().filter(|(_, snake)| snake.alive).rev() { //自己的蛇最后画,重叠时优先显示
+        for k in (0..snake.length as usize - 1).rev() {
+            let (x, y) = snake_cell(&snake.body[k]);
+            grid[y][x] = match (i == 0, k == 0) {
+                (true, false) => 5,
+                (true, true) => 6,
+                (false, false) => 7,
+                (false, true) => 8
+            };
+        }
+    }
+    let snake = &data.snakes[0];
+    let body = snake.body[..snake.length as usize - 1].iter().map(|segment| {
+        let (x, y) = snake_cell(segment);
+        Json::Array(vec![Json::Number(x as f64), Json::Number(y as f64)])
+    }).collect();
+    Json::Object(vec![
+        (String::from("grid"), Json::Array(grid.iter().map(|row| {
+            Json::Array(row.iter().map(|cell| Json::Number(*cell as f64)).collect())
+        }).collect())),
+        (String::from("body"), Json::Array(body)),
+        (String::from("direction"), Json::String(String::from(direction_name(snake.body[0].direction)))),
+        (String::from("hp"), Json::Number(data.stats[0].hp as f64)),
+        (String::from("length"), Json::Number((snake.length - 1) as f64)),
+        (String::from("food"), Json::Number(data.stats[0].food as f64)),
+        (String::from("tick"), Json::Number(data.tick as f64))
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::builtin_items;
+    use crate::test_game;
+
+    fn env() -> Env {
+        let options = simulate::parse_options(&[]).unwrap();
+        let rewards = Rewards { food: 1.0, damage: -0.5, death: -1.0, win: 5.0, step: 0.0 };
+        Env {
+            data: simulate::configure(test_game(builtin_items()), &options),
+            next_seed: options.seed,
+            options: options,
+            current: rewards.clone(),
+            rewards: rewards,
+            done: true
+        }
+    }
+
+    #[test]
+    fn accepts_only_whole_action_numbers() {
+        let mut env = env();
+        env.reset(&Json::Object(Vec::new())).unwrap();
+        for action in &[0.5, -0.5, 3.5, 4.0, -1.0] {
+            assert!(env.step(&Json::Number(*action)).is_err(), "{}", action);
+        }
+        assert!(env.step(&Json::Number(3.0)).is_ok());
+        assert!(env.step(&Json::String(String::from("up"))).is_ok());
+    }
+}
@@ -0,0 +1,638 @@
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+
+use crate::items::ItemKind;
+use crate::replay::Replay;
+use crate::{game_outcome, new_game, team_length, ColorName, ColorPairs, GameData, ItemType, BLINK_TICKS};
+
+const USAGE: &str = "用法：snake-rust export 回放文件 输出.svg|输出目录 [--cell 像素] [--every 步数]";
+const SIDEBAR: f64 = 14.0; //信息栏的宽度(格)
+const HOLD: i64 = 2000; //最后一帧停留的时间(毫秒)
+const BACKGROUND: [u8; 3] = [0, 0, 0];
+
+type Rgb = [u8; 3];
+
+fn rgb(color: ColorName) -> Rgb {//与终端中加粗显示的颜色一致
+    match color {
+        ColorName::Red => [239, 41, 41],
+        ColorName::Yellow => [252, 233, 79],
+        ColorName::Cyan => [52, 226, 226],
+        ColorName::White => [238, 238, 236],
+        ColorName::Green => [138, 226, 52]
+    }
+}
+
+enum Shape {
+    Rect(f64, f64, f64, f64, Rgb), //左上角、宽和高
+    Circle(f64, f64, f64, Rgb), //圆心和半径
+    Polygon(Vec<(f64, f64)>, Rgb),
+    Text(f64, f64, f64, String, Rgb) //左上角、字体放大倍数和文字,内置字体每个字符5×7点
+}
+
+struct Frame {
+    time: i64, //这一帧开始的时间(毫秒)
+    shapes: Vec<Shape>
+}
+
+pub fn run_export(args: &[String], items: Vec<ItemKind>) -> Result<(), String> {//不使用终端,把回放导出成SVG动画或者PNG图片序列
+    if args.len() < 2 {
+        return Err(String::from(USAGE));
+    }
+    let mut cell = 16.0;
+    let mut every = 1;
+    let mut i = 2;
+    while i < args.len() {
+        let value = args.get(i + 1).and_then(|value| value.parse::<u32>().ok()).filter(|value| *value > 0);
+        match args[i].as_str() {
+            "--cell" => cell = value.filter(|value| *value >= 4).ok_or(format!("--cell必须是不小于4的整数\n{}", USAGE))? as f64,
+            "--every" => every = value.ok_or(format!("--every必须是正整数\n{}", USAGE))?,
+            _ => return Err(format!("未知的参数：{}\n{}", args[i], USAGE))
+        }
+        i += 2;
+    }
+    let replay = Replay::load(&args[0])?;
+    let mut data = new_game(items, ColorPairs::default());
+    let (background, frames) = render(&mut data, &replay, cell, every);
+    let width = cell * (28.0 + SIDEBAR);
+    let height = cell * 24.0;
+    if args[1].ends_with(".svg") {
+        fs::write(&args[1], svg(width, height, &background, &frames)).map_err(|e| format!("无法写入{}：{}", args[1], e))
+    } else {
+        png_frames(Path::new(&args[1]), width as usize, height as usize, &background, &frames)
+    }
+}
+
+fn render(data: &mut GameData, replay: &Replay, cell: f64, every: u32) -> (Vec<Shape>, Vec<Frame>) {//重新计算整局,每隔every步画一帧
+    replay.start(data);
+    data.t1 = 0;
+    let mut frames = vec![Frame { time: 0, shapes: draw_frame(data, cell) }];
+    let mut time = 0;
+    while data.tick < replay.ticks && game_outcome(data).is_none() {
+        time += data.velocity as i64;
+        replay.advance(data);
+        data.t1 = time; //每一步都更新,限时玩法按回放中的时间判断是否结束
+        if data.tick % every == 0 || data.tick >= replay.ticks || game_outcome(data).is_some() {
+            frames.push(Frame { time: time, shapes: draw_frame(data, cell) });
+        }
+    }
+    frames.push(Frame { time: time + HOLD, shapes: Vec::new() }); //只用来记录最后一帧的结束时间
+    (draw_background(data, cell), frames)
+}
+
+fn cell_origin(x: usize, y: usize, cell: f64) -> (f64, f64) {//地图格子的左上角,四周留一格画隔墙
+    ((x as f64 + 1.0) * cell, (y as f64 + 1.0) * cell)
+}
+
+fn diamond(cx: f64, cy: f64, r: f64) -> Vec<(f64, f64)> {
+    vec![(cx, cy - r), (cx + r, cy), (cx, cy + r), (cx - r, cy)]
+}
+
+fn star(cx: f64, cy: f64, r: f64) -> Vec<(f64, f64)> {//五角星,内外两圈各5个点
+    (0..10).map(|i| {
+        let angle = std::f64::consts::PI * (i as f64 / 5.0 - 0.5);
+        let radius = if i % 2 == 0 { r } else { r * 0.4 };
+        (cx + radius * angle.cos(), cy + radius * angle.sin())
+    }).collect()
+}
+
+fn glyph_shape(glyph: &str, x: f64, y: f64, cell: f64, color: Rgb) -> Shape {//把终端中显示的字符换成图形,不认识的字符画成圆点
+    let (cx, cy) = (x + cell / 2.0, y + cell / 2.0);
+    let chars: Vec<char> = glyph.chars().collect();
+    match glyph {
+        "◆" | "♦" => Shape::Polygon(diamond(cx, cy, cell * 0.45), color),
+        "☆" | "★" => Shape::Polygon(star(cx, cy, cell * 0.48), color),
+        "■" | "□" => Shape::Rect(x + cell * 0.15, y + cell * 0.15, cell * 0.7, cell * 0.7, color),
+        "▲" | "△" => Shape::Polygon(vec![(cx, y + cell * 0.1), (x + cell * 0.9, y + cell * 0.85), (x + cell * 0.1, y + cell * 0.85)], color),
+        _ if chars.len() == 1 && chars[0].is_ascii_graphic() => {
+            let scale = (cell / 8.0).floor().max(1.0);
+            Shape::Text(cx - 2.5 * scale, cy - 3.5 * scale, scale, glyph.to_string(), color)
+        },
+        _ => Shape::Circle(cx, cy, cell * 0.38, color)
+    }
+}
+
+fn draw_background(data: &GameData, cell: f64) -> Vec<Shape> {//不会变化的部分:背景、隔墙和地图上的墙
+    let cyan = rgb(ColorName::Cyan);
+    let width = cell * (28.0 + SIDEBAR);
+    let height = cell * 24.0;
+    let line = (cell / 4.0).round().max(1.0);
+    let mut shapes = vec![Shape::Rect(0.0, 0.0, width, height, BACKGROUND)];
+    let half = cell / 2.0 - line / 2.0; //边框画在留出的一格中间
+    for &(x, y, w, h) in [(half, half, width - 2.0 * half, line), (half, height - half - line, width - 2.0 * half, line),
+        (half, half, line, height - 2.0 * half), (width - half - line, half, line, height - 2.0 * half),
+        (cell * 27.0 + half, half, line, height - 2.0 * half)].iter() {
+        shapes.push(Shape::Rect(x, y, w, h, cyan));
+    }
+    for x in 0..26 {
+        for y in 0..22 {
+            if data.map[x][y] == ItemType::Wall {
+                let (left, top) = cell_origin(x, y, cell);
+                shapes.push(Shape::Rect(left + 1.0, top + 1.0, cell - 2.0, cell - 2.0, cyan));
+            }
+        }
+    }
+    shapes
+}
+
+fn draw_frame(data: &GameData, cell: f64) -> Vec<Shape> {//一帧中会变化的部分:物品、蛇和信息栏
+    let mut shapes = Vec::new();
+    for x in 0..26 {
+        for y in 0..22 {
+            let left = data.expires[x][y].saturating_sub(data.tick);
+            if data.expires[x][y] > 0 && left <= BLINK_TICKS && left % 2 == 1 { //快要消失的物品闪烁
+                continue;
+            }
+            if let ItemType::Item(kind) = data.map[x][y] {
+                let (left, top) = cell_origin(x, y, cell);
+                shapes.push(glyph_shape(&data.items[kind].glyph, left, top, cell, rgb(data.items[kind].color)));
+            }
+        }
+    }
+    for snake in data.snakes.iter().filter(|snake| snake.alive) {
+        let length = snake.length as usize;
+        for i in 1..length - 1 {
+            let color = rgb(if i == length - 2 { snake.head_color } else { snake.body_color }); //蛇尾与蛇头同色
+            let (x, y) = crate::snake_cell(&snake.body[i]);
+            let (left, top) = cell_origin(x, y, cell);
+            shapes.push(Shape::Circle(left + cell / 2.0, top + cell / 2.0, cell * 0.42, color));
+        }
+        let (x, y) = crate::snake_cell(&snake.body[0]);
+        let (left, top) = cell_origin(x, y, cell);
+        shapes.push(Shape::Polygon(diamond(left + cell / 2.0, top + cell / 2.0, cell * 0.5), rgb(snake.head_color)));
+    }
+    draw_sidebar(data, cell, &mut shapes);
+    shapes
+}
+
+fn draw_sidebar(data: &GameData, cell: f64, shapes: &mut Vec<Shape>) {//内置字体只有ASCII字符,信息栏使用英文
+    let scale = (cell / 8.0).floor().max(1.0);
+    let yellow = rgb(ColorName::Yellow);
+    let red = rgb(ColorName::Red);
+    let left = cell * 28.5;
+    let line = 9.0 * scale + cell / 8.0;
+    let text = |shapes: &mut Vec<Shape>, column: usize, top: f64, text: String, color: Rgb| {//column是第几个字符
+        shapes.push(Shape::Text(left + column as f64 * 6.0 * scale, top, scale, text, color));
+    };
+    let mut top = cell;
+    text(shapes, 0, top, String::from("LEVEL"), yellow);
+    text(shapes, 6, top, data.level.to_string(), red);
+    top += line;
+    text(shapes, 0, top, String::from("TIME"), yellow);
+    text(shapes, 6, top, format!("{:.1}S", data.t1 as f64 / 1000.0), red);
+    top += line * 1.5;
+    let mut shown = Vec::new();
+    for (i, snake) in data.snakes.iter().enumerate() {
+        if shown.contains(&snake.stats) { //合作时两条蛇共用一份状态
+            continue;
+        }
+        if top + line * 3.0 > cell * 23.0 { //放不下的蛇不再输出
+            break;
+        }
+        shown.push(snake.stats);
+        let stats = &data.stats[snake.stats];
+        let name = if snake.player { format!("P{}", i + 1) } else { format!("AI{}", i + 1) };
+        shapes.push(Shape::Polygon(diamond(left + 2.5 * scale, top + 3.5 * scale, 3.5 * scale), rgb(snake.head_color)));
+        text(shapes, 2, top, if snake.alive { name } else { name + " X" }, rgb(snake.body_color));
+        text(shapes, 8, top, String::from("HP"), yellow);
+        text(shapes, 13, top, stats.hp.to_string(), red);
+        top += line;
+        text(shapes, 0, top, String::from("LEN"), yellow);
+        text(shapes, 4, top, team_length(data, snake.stats).to_string(), red);
+        text(shapes, 8, top, String::from("FOOD"), yellow);
+        text(shapes, 13, top, stats.food.to_string(), red);
+        top += line;
+        text(shapes, 0, top, String::from("SCORE"), yellow);
+        text(shapes, 6, top, stats.score.to_string(), red);
+        top += line * 1.5;
+    }
+}
+
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn svg_shape(out: &mut String, shape: &Shape) {
+    let _ = match shape {
+        Shape::Rect(x, y, w, h, color) => writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", x, y, w, h, hex(*color)),
+        Shape::Circle(x, y, r, color) => writeln!(out, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>", x, y, r, hex(*color)),
+        Shape::Polygon(points, color) => {
+            let points: Vec<String> = points.iter().map(|(x, y)| format!("{:.2},{:.2}", x, y)).collect();
+            writeln!(out, "<polygon points=\"{}\" fill=\"{}\"/>", points.join(" "), hex(*color))
+        },
+        Shape::Text(x, y, scale, text, color) => writeln!(out, "<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"{}\">{}</text>",
+            x, y + 7.0 * scale, 10.0 * scale, hex(*color), escape(text))
+    };
+}
+
+fn svg(width: f64, height: f64, background: &[Shape], frames: &[Frame]) -> String {//每一帧是一个组,按时间轮流显示,循环播放
+    let total = frames[frames.len() - 1].time as f64 / 1000.0;
+    let mut out = String::new();
+    let _ = writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"monospace\">",
+        width, height, width, height);
+    for shape in background {
+        svg_shape(&mut out, shape);
+    }
+    let count = frames.len() - 1;
+    for i in 0..count {
+        let start = frames[i].time as f64 / 1000.0 / total;
+        let end = frames[i + 1].time as f64 / 1000.0 / total;
+        let (values, times) = if count == 1 {
+            (String::from("inline"), String::from("0"))
+        } else if i == 0 {
+            (String::from("inline;none"), format!("0;{:.6}", end))
+        } else if i == count - 1 {
+            (String::from("none;inline"), format!("0;{:.6}", start))
+        } else {
+            (String::from("none;inline;none"), format!("0;{:.6};{:.6}", start, end))
+        };
+        let _ = writeln!(out, "<g display=\"{}\">", if i == 0 { "inline" } else { "none" });
+        let _ = writeln!(out, "<animate attributeName=\"display\" values=\"{}\" keyTimes=\"{}\" dur=\"{:.3}s\" calcMode=\"discrete\" repeatCount=\"indefinite\"/>",
+            values, times, total);
+        for shape in frames[i].shapes.iter() {
+            svg_shape(&mut out, shape);
+        }
+        out += "</g>\n";
+    }
+    out += "</svg>\n";
+    out
+}
+
+fn png_frames(dir: &Path, width: usize, height: usize, background: &[Shape], frames: &[Frame]) -> Result<(), String> {//每一帧一张PNG,frames.txt记录每一帧的时长,可以直接交给ffmpeg的concat
+    fs::create_dir_all(dir).map_err(|e| format!("无法创建{}：{}", dir.display(), e))?;
+    let mut base = Canvas::new(width, height);
+    for shape in background {
+        base.draw(shape);
+    }
+    let mut list = String::from("ffconcat version 1.0\n");
+    for i in 0..frames.len() - 1 {
+        let mut canvas = base.clone();
+        for shape in frames[i].shapes.iter() {
+            canvas.draw(shape);
+        }
+        let name = format!("frame{:05}.png", i);
+        fs::write(dir.join(&name), canvas.png()).map_err(|e| format!("无法写入{}：{}", name, e))?;
+        list += &format!("file '{}'\nduration {:.3}\n", name, (frames[i + 1].time - frames[i].time) as f64 / 1000.0);
+    }
+    fs::write(dir.join("frames.txt"), list).map_err(|e| format!("无法写入frames.txt：{}", e))
+}
+
+#[derive(Clone)]
+struct Canvas {//软件光栅化,每个像素取中心点判断是否在图形内
+    width: usize,
+    height: usize,
+    pixels: Vec<u8> //RGB,按行排列
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas { width: width, height: height, pixels: vec![0; width * height * 3] }
+    }
+
+    fn fill<F: Fn(f64, f64) -> bool>(&mut self, left: f64, top: f64, right: f64, bottom: f64, color: Rgb, inside: F) {
+        let x0 = left.floor().max(0.0) as usize;
+        let y0 = top.floor().max(0.0) as usize;
+        let x1 = (right.ceil().max(0.0) as usize).min(self.width);
+        let y1 = (bottom.ceil().max(0.0) as usize).min(self.height);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if inside(x as f64 + 0.5, y as f64 + 0.5) {
+                    let i = (y * self.width + x) * 3;
+                    self.pixels[i..i + 3].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, shape: &Shape) {
+        match shape {
+            Shape::Rect(x, y, w, h, color) => self.fill(*x, *y, x + w, y + h, *color, |_, _| true),
+            Shape::Circle(cx, cy, r, color) => {
+                let (cx, cy, r) = (*cx, *cy, *r);
+                self.fill(cx - r, cy - r, cx + r, cy + r, *color, |x, y| (x - cx) * (x - cx) + (y - cy) * (y - cy) <= r * r);
+            },
+            Shape::Polygon(points, color) => {
+                let left = points.iter().map(|p| p.0).fold(f64::MAX, f64::min);
+                let right = points.iter().map(|p| p.0).fold(f64::MIN, f64::max);
+                let top = points.iter().map(|p| p.1).fold(f64::MAX, f64::min);
+                let bottom = points.iter().map(|p| p.1).fold(f64::MIN, f64::max);
+                self.fill(left, top, right, bottom, *color, |x, y| inside_polygon(points, x, y));
+            },
+            Shape::Text(x, y, scale, text, color) => {
+                for (i, c) in text.chars().enumerate() {
+                    let rows = font(c);
+                    for row in 0..7 {
+                        for column in 0..5 {
+                            if rows[row] & (0x10 >> column) != 0 {
+                                let left = x + (i as f64 * 6.0 + column as f64) * scale;
+                                let top = y + row as f64 * scale;
+                                self.fill(left, top, left + scale, top + scale, *color, |_, _| true);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn png(&self) -> Vec<u8> {//不压缩滤波,用deflate的固定编码压缩
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&(self.width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.height as u32).to_be_bytes());
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); //8位RGB
+        let mut out = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        chunk(&mut out, b"IHDR", &ihdr);
+        chunk(&mut out, b"IDAT", &zlib(&raw, self.width * 3 + 1));
+        chunk(&mut out, b"IEND", &[]);
+        out
+    }
+}
+
+fn inside_polygon(points: &[(f64, f64)], x: f64, y: f64) -> bool {//射线法
+    let mut inside = false;
+    let mut j = points.len() - 1;
+    for i in 0..points.len() {
+        let (xi, yi) = points[i];
+        let (xj, yj) = points[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+struct BitWriter {//deflate的位从低到高写入
+    bytes: Vec<u8>,
+    current: u32,
+    count: u32
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.current |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.current as u8);
+            self.current >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn write_code(&mut self, code: u32, bits: u32) {//哈夫曼编码从高位开始写
+        let mut reversed = 0;
+        for i in 0..bits {
+            reversed |= ((code >> i) & 1) << (bits - 1 - i);
+        }
+        self.write(reversed, bits);
+    }
+
+    fn literal(&mut self, value: u32) {//固定哈夫曼编码表
+        match value {
+            0..=143 => self.write_code(0x30 + value, 8),
+            144..=255 => self.write_code(0x190 + value - 144, 9),
+            256..=279 => self.write_code(value - 256, 7),
+            _ => self.write_code(0xC0 + value - 280, 8)
+        }
+    }
+}
+
+const LENGTH_BASE: [u32; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u32; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073,
+    4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u32; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+fn zlib(data: &[u8], stride: usize) -> Vec<u8> {//图片大多是大块的纯色,只和前一个像素、上一行以及上次出现的位置比较就能压缩得很好
+    const HASH_SIZE: usize = 1 << 15;
+    let mut writer = BitWriter { bytes: vec![0x78, 0x01], current: 0, count: 0 };
+    writer.write(1, 1); //最后一块
+    writer.write(1, 2); //固定哈夫曼编码
+    let mut last = vec![usize::MAX; HASH_SIZE];
+    let hash = |i: usize| ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & (HASH_SIZE - 1);
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            for &start in [last[h], i.wrapping_sub(3), i.wrapping_sub(stride)].iter().filter(|&&start| start < i && i - start <= 32768) {
+                let mut length = 0;
+                while length < 258 && i + length < data.len() && data[start + length] == data[i + length] {
+                    length += 1;
+                }
+                if length > best.0 {
+                    best = (length, i - start);
+                }
+            }
+            last[h] = i;
+        }
+        if best.0 >= 3 {
+            let (length, distance) = best;
+            let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+            writer.literal(257 + code as u32);
+            writer.write(length as u32 - LENGTH_BASE[code], LENGTH_EXTRA[code]);
+            let code = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+            writer.write_code(code as u32, 5);
+            writer.write(distance as u32 - DISTANCE_BASE[code], DISTANCE_EXTRA[code]);
+            for k in i + 1..(i + length).min(data.len().saturating_sub(2)) {
+                last[hash(k)] = k;
+            }
+            i += length;
+        } else {
+            writer.literal(data[i] as u32);
+            i += 1;
+        }
+    }
+    writer.literal(256); //块结束
+    writer.write(0, 7); //补齐最后一个字节
+    let mut out = writer.bytes;
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn font(c: char) -> [u8; 7] {//5×7点阵,每行低5位从左到右
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ' ' => [0; 7],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '$' => [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04],
+        '@' => [0x0E, 0x11, 0x17, 0x15, 0x17, 0x10, 0x0F],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        '^' => [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04] //没有的字符显示成问号
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        position: usize
+    }
+
+    impl<'a> BitReader<'a> {
+        fn bits(&mut self, count: usize) -> usize {//低位在前
+            let mut value = 0;
+            for i in 0..count {
+                value |= ((self.bytes[self.position / 8] >> (self.position % 8)) as usize & 1) << i;
+                self.position += 1;
+            }
+            value
+        }
+
+        fn code(&mut self, count: usize) -> usize {//哈夫曼编码高位在前
+            (0..count).fold(0, |code, _| code << 1 | self.bits(1))
+        }
+
+        fn symbol(&mut self) -> usize {//固定哈夫曼编码表
+            let code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code;
+            }
+            let code = code << 1 | self.bits(1);
+            match code {
+                0x30..=0xBF => code - 0x30,
+                0xC0..=0xC7 => 280 + code - 0xC0,
+                _ => 144 + (code << 1 | self.bits(1)) - 0x190
+            }
+        }
+    }
+
+    fn inflate(zlib: &[u8]) -> Vec<u8> {//只支持zlib()写出的单个固定编码块
+        assert_eq!(&zlib[..2], &[0x78, 0x01]);
+        let mut reader = BitReader { bytes: &zlib[2..zlib.len() - 4], position: 0 };
+        assert_eq!(reader.bits(3), 0b011);
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            match reader.symbol() {
+                literal @ 0..=255 => out.push(literal as u8),
+                256 => break,
+                symbol => {
+                    let code = symbol - 257;
+                    let length = LENGTH_BASE[code] as usize + reader.bits(LENGTH_EXTRA[code] as usize);
+                    let code = reader.code(5);
+                    let distance = DISTANCE_BASE[code] as usize + reader.bits(DISTANCE_EXTRA[code] as usize);
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+        let checksum = &zlib[zlib.len() - 4..];
+        assert_eq!(checksum, &adler32(&out).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn zlib_round_trips() {
+        let mut image = Vec::new();
+        for y in 0..40 {
+            image.push(0);
+            for x in 0..90 {
+                image.push(if (x / 9 + y / 4) % 2 == 0 { 0x20 } else { (x * y % 251) as u8 });
+            }
+        }
+        let long: Vec<u8> = (0..70000).map(|i| (i / 300 % 7) as u8).collect();
+        for data in [Vec::new(), b"ab".to_vec(), b"abcabcabcabcabcabc".to_vec(), (0..=255).collect(), image, long].iter() {
+            let compressed = zlib(data, 91);
+            assert_eq!(&inflate(&compressed), data);
+        }
+    }
+}
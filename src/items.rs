@@ -0,0 +1,205 @@
+use std::convert::TryFrom;
+use std::fs;
+
+use crate::level::MAX_LENGTH;
+use crate::ColorName;
+
+pub const BARRIER: usize = 2; //物品表依次是内置的食物、星星和障碍物,地图文件中的*就是障碍物
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Effect {
+    Grow(i16), //蛇身变长
+    Shrink(i16), //蛇身变短
+    Heal(i8), //生命值增加
+    Damage(i8), //生命值减少
+    Speed(i32), //移动间隔变化(毫秒),负数表示变快
+    Score(i32) //额外得分
+}
+
+impl Effect {
+    pub fn to_text(&self) -> String {//与parse_effect对应的写法,例如"grow 2"
+        match self {
+            Effect::Grow(n) => format!("grow {}", n),
+            Effect::Shrink(n) => format!("shrink {}", n),
+            Effect::Heal(n) => format!("heal {}", n),
+            Effect::Damage(n) => format!("damage {}", n),
+            Effect::Speed(n) => format!("speed {}", n),
+            Effect::Score(n) => format!("score {}", n)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ItemKind {
+    pub name: String, //配置文件中使用的名字
+    pub glyph: String, //显示的字符
+    pub color: ColorName, //显示的颜色
+    pub chance: u32, //每一步出现的概率(千分之几)
+    pub interval: u32, //每隔多少步出现一次,0表示不按间隔出现
+    pub keep: u32, //地图上始终保持的数量
+    pub max: u32, //地图上最多同时存在的数量,0表示不限
+    pub lifetime: u32, //出现后存在的步数,0表示一直存在
+    pub effects: Vec<Effect> //吃到后的效果
+}
+
+impl ItemKind {
+    fn new(name: &str, glyph: &str, color: ColorName) -> ItemKind {
+        ItemKind {
+            name: name.to_string(),
+            glyph: glyph.to_string(),
+            color: color,
+            chance: 0,
+            interval: 0,
+            keep: 0,
+            max: 0,
+            lifetime: 0,
+            effects: Vec::new()
+        }
+    }
+
+    pub fn grows(&self) -> bool {//能让蛇变长的物品算作食物
+        self.effects.iter().any(|effect| match effect {
+            Effect::Grow(_) => true,
+            _ => false
+        })
+    }
+
+    pub fn heals(&self) -> bool {//能恢复生命值的物品,例如小星星
+        self.effects.iter().any(|effect| match effect {
+            Effect::Heal(_) => true,
+            _ => false
+        })
+    }
+
+    pub fn damages(&self) -> bool {//会让生命值减少的物品
+        self.effects.iter().any(|effect| match effect {
+            Effect::Damage(_) => true,
+            _ => false
+        })
+    }
+}
+
+pub fn builtin_items() -> Vec<ItemKind> {
+    let mut food = ItemKind::new("food", "●", ColorName::Yellow);
+    food.keep = 1;
+    food.effects.push(Effect::Grow(1));
+    let mut star = ItemKind::new("star", "☆", ColorName::Green);
+    star.chance = 60;
+    star.max = 3;
+    star.lifetime = 80;
+    star.effects.push(Effect::Heal(1));
+    let mut barrier = ItemKind::new("barrier", "*", ColorName::Red);
+    barrier.chance = 80;
+    barrier.max = 30;
+    barrier.lifetime = 200;
+    barrier.effects.push(Effect::Damage(1));
+    vec![food, star, barrier]
+}
+
+pub fn load_items(path: &str) -> Result<Vec<ItemKind>, String> {//读取物品配置,文件不存在时使用内置物品
+    let mut items = builtin_items();
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => return Ok(items)
+    };
+    let mut current: Option<usize> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') { //[名字]开始一种物品,名字与内置物品相同时修改内置物品
+            let name = &line[1..line.len() - 1];
+            current = Some(match items.iter().position(|item| item.name == name) {
+                Some(index) => index,
+                None => {
+                    items.push(ItemKind::new(name, "?", ColorName::White));
+                    items.len() - 1
+                }
+            });
+            continue;
+        }
+        let item = match current {
+            Some(index) => &mut items[index],
+            None => return Err(format!("{}：设置前缺少[物品名]", line))
+        };
+        let (key, value) = match line.find('=') {
+            Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+            None => return Err(format!("无法识别：{}", line))
+        };
+        match key {
+            "glyph" => item.glyph = value.to_string(),
+            "color" => item.color = ColorName::parse(value).ok_or(format!("未知的颜色：{}", value))?,
+            "chance" => item.chance = value.parse().map_err(|_| "chance必须是数字")?,
+            "interval" => item.interval = value.parse().map_err(|_| "interval必须是数字")?,
+            "keep" => item.keep = value.parse().map_err(|_| "keep必须是数字")?,
+            "max" => item.max = value.parse().map_err(|_| "max必须是数字")?,
+            "lifetime" => item.lifetime = value.parse().map_err(|_| "lifetime必须是数字")?,
+            "effects" => {
+                item.effects.clear();
+                for effect in value.split(',').map(|effect| effect.trim()).filter(|effect| !effect.is_empty()) {
+                    item.effects.push(parse_effect(effect)?);
+                }
+            },
+            _ => return Err(format!("未知的设置：{}", key))
+        }
+    }
+    Ok(items)
+}
+
+pub fn parse_effect(text: &str) -> Result<Effect, String> {//解析"grow 2"这样的效果
+    let mut parts = text.split_whitespace();
+    let name = parts.next().unwrap_or("");
+    let amount: i32 = match parts.next() {
+        Some(value) => value.parse().map_err(|_| format!("效果数值必须是数字：{}", text))?,
+        None => 1
+    };
+    let range = || format!("效果数值超出范围：{}", text);
+    let length = || i16::try_from(amount).ok().filter(|n| *n >= 1 && *n <= MAX_LENGTH).ok_or_else(range); //长度变化为1到蛇身最大长度
+    let hp = || i8::try_from(amount).ok().filter(|n| *n >= 1 && *n <= 6).ok_or_else(range); //生命值变化为1到6
+    match name {
+        "grow" => Ok(Effect::Grow(length()?)),
+        "shrink" => Ok(Effect::Shrink(length()?)),
+        "heal" => Ok(Effect::Heal(hp()?)),
+        "damage" => Ok(Effect::Damage(hp()?)),
+        "speed" => Ok(Effect::Speed(amount)),
+        "score" => Ok(Effect::Score(amount)),
+        _ => Err(format!("未知的效果：{}", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_effects() {
+        assert_eq!(parse_effect("grow 2"), Ok(Effect::Grow(2)));
+        assert_eq!(parse_effect("heal"), Ok(Effect::Heal(1)));
+        assert_eq!(parse_effect("speed -30"), Ok(Effect::Speed(-30)));
+        assert_eq!(parse_effect("score 100000"), Ok(Effect::Score(100000)));
+    }
+
+    #[test]
+    fn round_trips_effect_text() {
+        for effect in [Effect::Grow(3), Effect::Shrink(1), Effect::Heal(2), Effect::Damage(6), Effect::Speed(-20), Effect::Score(50)].iter() {
+            assert_eq!(parse_effect(&effect.to_text()), Ok(*effect));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_effects() {
+        assert!(parse_effect("heal 300").is_err());
+        assert!(parse_effect("damage -129").is_err());
+        assert!(parse_effect("grow 40000").is_err());
+        assert!(parse_effect("grow 82").is_err());
+        assert!(parse_effect("grow -5").is_err());
+        assert!(parse_effect("shrink -100").is_err());
+        assert!(parse_effect("shrink 0").is_err());
+        assert!(parse_effect("heal 127").is_err());
+        assert!(parse_effect("damage -128").is_err());
+        assert!(parse_effect("damage 7").is_err());
+        assert!(parse_effect("grow two").is_err());
+        assert!(parse_effect("fly 1").is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>) //保持键的顺序
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {//输出为一行JSON
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => {
+                if n.is_finite() {
+                    write!(f, "{}", n)
+                } else {
+                    write!(f, "null")
+                }
+            },
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+    write!(f, "\"")
+}
+
+pub fn parse(text: &str) -> Result<Json, String> {//解析一段完整的JSON
+    let chars: Vec<char> = text.chars().collect();
+    let mut parser = Parser { chars: chars, pos: 0, depth: 0 };
+    let value = parser.value()?;
+    parser.skip_space();
+    if parser.pos < parser.chars.len() {
+        return Err(format!("JSON第{}个字符之后有多余的内容", parser.pos));
+    }
+    Ok(value)
+}
+
+const MAX_DEPTH: usize = 64; //数组和对象最多嵌套的层数,防止恶意的输入耗尽栈空间
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize //当前嵌套的层数
+}
+
+impl Parser {
+    fn skip_space(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("JSON第{}个字符处应该是'{}'", self.pos, c))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.pos + word.chars().count();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().collect::<String>() == word {
+            self.pos = end;
+            Ok(value)
+        } else {
+            Err(format!("JSON第{}个字符处无法识别", self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!("JSON嵌套超过{}层", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let value = self.nested();
+        self.depth -= 1;
+        value
+    }
+
+    fn nested(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    if self.peek() != Some('"') {
+                        return Err(format!("JSON第{}个字符处应该是键名", self.pos));
+                    }
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(fields));
+                        },
+                        _ => return Err(format!("JSON第{}个字符处应该是','或'}}'", self.pos))
+                    }
+                }
+            },
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        },
+                        _ => return Err(format!("JSON第{}个字符处应该是','或']'", self.pos))
+                    }
+                }
+            },
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let start = self.pos;
+                while self.pos < self.chars.len() && (self.chars[self.pos].is_ascii_digit() || "+-.eE".contains(self.chars[self.pos])) {
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start..self.pos].iter().collect();
+                text.parse().map(Json::Number).map_err(|_| format!("JSON中无法识别的数字：{}", text))
+            },
+            Some(_) => Err(format!("JSON第{}个字符处无法识别", self.pos)),
+            None => Err(String::from("JSON不完整"))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1; //跳过开头的引号
+        let mut s = String::new();
+        loop {
+            let c = match self.chars.get(self.pos) {
+                Some(c) => *c,
+                None => return Err(String::from("JSON字符串没有结束"))
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let escaped = self.chars.get(self.pos).cloned().ok_or("JSON字符串没有结束")?;
+                    self.pos += 1;
+                    match escaped {
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xD800..0xDC00).contains(&code) && self.chars.get(self.pos) == Some(&'\\') && self.chars.get(self.pos + 1) == Some(&'u') {
+                                let start = self.pos;
+                                self.pos += 2;
+                                match self.hex4()? {
+                                    low @ 0xDC00..=0xDFFF => code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00), //代理对合成一个字符
+                                    _ => self.pos = start //不是低位代理,留给下一次处理
+                                }
+                            }
+                            s.push(std::char::from_u32(code).unwrap_or('\u{fffd}')); //落单的代理换成替换字符
+                        },
+                        other => s.push(other)
+                    }
+                },
+                c => s.push(c)
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {//读取\u后面的四位十六进制数
+        if self.pos + 4 > self.chars.len() {
+            return Err(String::from("JSON字符串没有结束"));
+        }
+        let hex: String = self.chars[self.pos..self.pos + 4].iter().collect();
+        self.pos += 4;
+        u32::from_str_radix(&hex, 16).map_err(|_| format!("JSON中无法识别的转义：\\u{}", hex))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_values() {
+        let text = r#"{"a":[1,-2.5,true,false,null],"b":"x\"y\\z\n","c":{}}"#;
+        let json = parse(text).unwrap();
+        assert_eq!(json.to_string(), text);
+        assert_eq!(json.get("a"), Some(&Json::Array(vec![Json::Number(1.0), Json::Number(-2.5), Json::Bool(true), Json::Bool(false), Json::Null])));
+        assert_eq!(json.get("b").and_then(Json::as_str), Some("x\"y\\z\n"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(parse("").is_err());
+        assert!(parse("[1,]").is_err());
+        assert!(parse("{\"a\" 1}").is_err());
+        assert!(parse("\"abc").is_err());
+        assert!(parse("[1] 2").is_err());
+        assert!(parse("tru").is_err());
+    }
+
+    #[test]
+    fn limits_nesting_depth() {
+        assert!(parse(&format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH))).is_ok());
+        assert!(parse(&format!("{}{}", "[".repeat(MAX_DEPTH + 1), "]".repeat(MAX_DEPTH + 1))).is_err());
+        assert!(parse(&"[".repeat(1_000_000)).is_err());
+    }
+
+    #[test]
+    fn decodes_unicode_escapes() {
+        assert_eq!(parse(r#""\u86c7""#).unwrap(), Json::String(String::from("蛇")));
+        assert_eq!(parse(r#""\uD83D\uDE00""#).unwrap(), Json::String(String::from("\u{1F600}")));
+        assert_eq!(parse(r#""\ud83d\u0041""#).unwrap(), Json::String(String::from("\u{fffd}A")));
+        assert_eq!(parse(r#""\uDE00""#).unwrap(), Json::String(String::from("\u{fffd}")));
+        assert!(parse(r#""\u12""#).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs;
+
+use crate::items::BARRIER;
+use crate::rules::WallRule;
+use crate::{Direction, ItemType};
+
+pub const MAP_WIDTH: usize = 26; //地图宽度(格)
+pub const MAP_HEIGHT: usize = 22; //地图高度(格)
+pub const MAX_LENGTH: i16 = 81; //蛇身最大长度
+pub const MIN_VELOCITY: i32 = 20; //地图中能设置的最小移动间隔(毫秒)
+pub const MAX_VELOCITY: i32 = 1000; //地图中能设置的最大移动间隔(毫秒)
+
+#[derive(Clone)]
+pub struct Level {
+    pub number: i8, //关卡编号,自定义地图为0
+    pub name: String, //关卡名称
+    pub velocity: i32, //蛇的移动速度
+    pub goal_length: i16, //达到此长度即过关
+    pub goal_food: i16, //吃到此数量的食物即过关,0表示不限
+    pub wall: Option<WallRule>, //本关的撞墙规则,为None时使用全局规则
+    pub spawn: (usize, usize, Direction), //蛇头出生的格子和方向
+    pub spawn2: Option<(usize, usize, Direction)>, //双人游戏时玩家2的出生点,为None时与玩家1对称
+    pub cells: [[ItemType; MAP_HEIGHT]; MAP_WIDTH] //地图上的墙和障碍物
+}
+
+impl Level {
+    pub fn blank() -> Level {
+        Level {
+            number: 0,
+            name: String::from("未命名"),
+            velocity: 200,
+            goal_length: MAX_LENGTH,
+            goal_food: 0,
+            wall: None,
+            spawn: (2, 9, Direction::Right),
+            spawn2: None,
+            cells: [[ItemType::None; MAP_HEIGHT]; MAP_WIDTH]
+        }
+    }
+
+    pub fn builtin(number: i8) -> Level {
+        let velocity = match number { //根据关数来设定蛇的移动速度
+            1 => 600,
+            2 => 400,
+            3 => 200,
+            4 => 150,
+            5 => 100,
+            6 => 60,
+            _ => panic!()
+        };
+        Level {
+            number: number,
+            name: format!("第{}关", number),
+            velocity: velocity,
+            ..Level::blank()
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Level, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("无法读取{}：{}", path, e))?;
+        Level::parse(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("无法保存{}：{}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Level, String> {
+        let mut level = Level::blank();
+        let mut lines = text.lines();
+        let mut has_spawn = false;
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "map:" {
+                for y in 0..MAP_HEIGHT {
+                    let row: Vec<char> = lines.next().ok_or("地图行数不足")?.chars().collect();
+                    if row.len() != MAP_WIDTH {
+                        return Err(format!("地图第{}行应为{}格", y + 1, MAP_WIDTH));
+                    }
+                    for x in 0..MAP_WIDTH {
+                        level.cells[x][y] = match row[x] {
+                            '.' => ItemType::None,
+                            '#' => ItemType::Wall,
+                            '*' => ItemType::Item(BARRIER),
+                            c => match spawn_direction(c) {
+                                Some(direction) => {
+                                    level.spawn = (x, y, direction);
+                                    has_spawn = true;
+                                    ItemType::None
+                                },
+                                None => return Err(format!("地图中有未知字符'{}'", c))
+                            }
+                        };
+                    }
+                }
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => return Err(format!("无法识别：{}", line))
+            };
+            match key {
+                "name" => level.name = value.to_string(),
+                "speed" => {
+                    level.velocity = value.parse().map_err(|_| "速度必须是数字")?;
+                    if level.velocity < MIN_VELOCITY || level.velocity > MAX_VELOCITY {
+                        return Err(format!("速度必须在{}到{}毫秒之间", MIN_VELOCITY, MAX_VELOCITY));
+                    }
+                },
+                "goal_length" => level.goal_length = value.parse().map_err(|_| "目标长度必须是数字")?,
+                "goal_food" => level.goal_food = value.parse().map_err(|_| "目标食物必须是数字")?,
+                "spawn2" => level.spawn2 = Some(parse_spawn(value).ok_or("spawn2的格式应为x,y,方向")?),
+                "wall" => level.wall = Some(WallRule::parse(value).ok_or("撞墙规则只能是wrap、solid或free")?),
+                _ => return Err(format!("未知的设置：{}", key))
+            }
+        }
+        if !has_spawn {
+            return Err(String::from("地图中没有出生点"));
+        }
+        let (x, y, _) = level.second_spawn();
+        if (x, y) == (level.spawn.0, level.spawn.1) {
+            return Err(String::from("玩家2的出生点不能和玩家1的重合"));
+        }
+        if level.goal_length > MAX_LENGTH || level.goal_length < 5 {
+            level.goal_length = MAX_LENGTH;
+        }
+        Ok(level)
+    }
+
+    pub fn second_spawn(&self) -> (usize, usize, Direction) {//玩家2的出生点
+        self.spawn2.unwrap_or_else(|| {
+            let (x, y, direction) = self.spawn;
+            let direction = match direction {
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left,
+                Direction::Up => Direction::Down,
+                Direction::Down => Direction::Up
+            };
+            (MAP_WIDTH - 1 - x, MAP_HEIGHT - 1 - y, direction)
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# snake-rust map\n");
+        text += &format!("name={}\n", self.name);
+        text += &format!("speed={}\n", self.velocity);
+        text += &format!("goal_length={}\n", self.goal_length);
+        text += &format!("goal_food={}\n", self.goal_food);
+        if let Some(wall) = self.wall {
+            text += &format!("wall={}\n", wall.key());
+        }
+        if let Some((x, y, direction)) = self.spawn2 {
+            text += &format!("spawn2={},{},{}\n", x, y, spawn_char(direction));
+        }
+        text += "map:\n";
+        for y in 0..MAP_HEIGHT {
+            for x in 0..MAP_WIDTH {
+                text.push(if (x, y) == (self.spawn.0, self.spawn.1) {
+                    spawn_char(self.spawn.2)
+                } else {
+                    match self.cells[x][y] {
+                        ItemType::Wall => '#',
+                        ItemType::Item(BARRIER) => '*',
+                        _ => '.'
+                    }
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+pub fn spawn_char(direction: Direction) -> char {
+    match direction {
+        Direction::Left => '<',
+        Direction::Right => '>',
+        Direction::Up => '^',
+        Direction::Down => 'v'
+    }
+}
+
+fn parse_spawn(text: &str) -> Option<(usize, usize, Direction)> {//解析"x,y,方向"格式的出生点
+    let parts: Vec<&str> = text.split(',').map(|part| part.trim()).collect();
+    if parts.len() != 3 || parts[2].chars().count() != 1 {
+        return None;
+    }
+    let x: usize = parts[0].parse().ok()?;
+    let y: usize = parts[1].parse().ok()?;
+    if x >= MAP_WIDTH || y >= MAP_HEIGHT {
+        return None;
+    }
+    Some((x, y, spawn_direction(parts[2].chars().next()?)?))
+}
+
+pub fn spawn_direction(c: char) -> Option<Direction> {
+    match c {
+        '<' => Some(Direction::Left),
+        '>' => Some(Direction::Right),
+        '^' => Some(Direction::Up),
+        'v' => Some(Direction::Down),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_text(settings: &str, rows: &[(usize, &str)]) -> String {//rows中列出的行替换空地
+        let mut text = format!("{}\nmap:\n", settings);
+        for y in 0..MAP_HEIGHT {
+            text += rows.iter().find(|(row, _)| *row == y).map_or("..........................", |(_, line)| *line);
+            text.push('\n');
+        }
+        text
+    }
+
+    #[test]
+    fn parses_settings_and_cells() {
+        let text = map_text("# 注释\nname=挑战1\nspeed=150\ngoal_length=30\ngoal_food=12\nwall=solid\nspawn2=23,12,<",
+            &[(0, "#........................."), (9, "..>.......*...............")]);
+        let level = Level::parse(&text).unwrap();
+        assert_eq!(level.name, "挑战1");
+        assert_eq!(level.velocity, 150);
+        assert_eq!(level.goal_length, 30);
+        assert_eq!(level.goal_food, 12);
+        assert_eq!(level.wall, Some(WallRule::Solid));
+        assert_eq!(level.spawn, (2, 9, Direction::Right));
+        assert_eq!(level.second_spawn(), (23, 12, Direction::Left));
+        assert_eq!(level.cells[0][0], ItemType::Wall);
+        assert_eq!(level.cells[10][9], ItemType::Item(BARRIER));
+        assert_eq!(level.cells[2][9], ItemType::None);
+    }
+
+    #[test]
+    fn round_trips_text() {
+        let text = map_text("name=往返\nspeed=80\nwall=free", &[(3, "....####.................."), (20, "......................^...")]);
+        let level = Level::parse(&text).unwrap();
+        assert_eq!(Level::parse(&level.to_text()).unwrap().to_text(), level.to_text());
+        assert_eq!(level.second_spawn(), (MAP_WIDTH - 1 - 22, MAP_HEIGHT - 1 - 20, Direction::Down));
+    }
+
+    #[test]
+    fn rejects_bad_maps() {
+        let spawn = [(9, "..>.......................")];
+        assert!(Level::parse(&map_text("", &[])).is_err()); //没有出生点
+        assert!(Level::parse(&map_text("speed=5", &spawn)).is_err());
+        assert!(Level::parse(&map_text("speed=fast", &spawn)).is_err());
+        assert!(Level::parse(&map_text("spawn2=26,0,>", &spawn)).is_err());
+        assert!(Level::parse(&map_text("spawn2=2,9,<", &spawn)).is_err());
+        assert!(Level::parse(&map_text("color=red", &spawn)).is_err());
+        assert!(Level::parse(&map_text("", &[(9, "..>.....x.................")])).is_err());
+        assert!(Level::parse("speed=100\nmap:\n..>.......................\n").is_err()); //行数不足
+        assert_eq!(Level::parse(&map_text("goal_length=200", &spawn)).unwrap().goal_length, MAX_LENGTH);
+    }
+}
@@ -0,0 +1,273 @@
+use std::fs;
+use std::path::PathBuf;
+
+use ncursesw::*;
+
+use crate::json::Json;
+use crate::{addstr, clear_screen, fit, move_cursor, read_line, set_color, set_cursor_visiable, GameData};
+
+pub struct Lifetime {//所有玩过的游戏累计的统计
+    path: PathBuf, //统计文件
+    pub games: Vec<(String, u32)>, //每个关卡玩过的局数,关卡的写法与排行榜相同
+    pub food: u64, //吃到的食物
+    pub stars: u64, //吃到的能恢复生命值的物品
+    pub millis: i64, //总用时(毫秒)
+    pub ticks: u64, //总共存活的步数
+    pub longest: i16, //最长的蛇
+    pub deaths: Vec<(String, u32)> //每种死亡原因的次数
+}
+
+pub struct GameRecord {//一局结束时玩家1的情况
+    pub table: String, //关卡
+    pub food: i16,
+    pub stars: i16,
+    pub millis: i64,
+    pub ticks: u32,
+    pub length: i16,
+    pub death: Option<String> //死亡原因,过关或获胜时为None
+}
+
+fn add(counts: &mut Vec<(String, u32)>, key: &str) {
+    match counts.iter_mut().find(|(name, _)| name == key) {
+        Some((_, count)) => *count += 1,
+        None => counts.push((key.to_string(), 1))
+    }
+}
+
+impl Lifetime {
+    pub fn load(path: PathBuf) -> Lifetime {//文件不存在或无法解析的行会被忽略
+        let mut lifetime = Lifetime { path: path, games: Vec::new(), food: 0, stars: 0, millis: 0, ticks: 0, longest: 0, deaths: Vec::new() };
+        let text = fs::read_to_string(&lifetime.path).unwrap_or_default();
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match (fields[0], fields.len()) {
+                ("food", 2) => lifetime.food = fields[1].parse().unwrap_or(0),
+                ("stars", 2) => lifetime.stars = fields[1].parse().unwrap_or(0),
+                ("millis", 2) => lifetime.millis = fields[1].parse().unwrap_or(0),
+                ("ticks", 2) => lifetime.ticks = fields[1].parse().unwrap_or(0),
+                ("longest", 2) => lifetime.longest = fields[1].parse().unwrap_or(0),
+                ("games", 3) => lifetime.games.push((fields[1].to_string(), fields[2].parse().unwrap_or(0))),
+                ("deaths", 3) => lifetime.deaths.push((fields[1].to_string(), fields[2].parse().unwrap_or(0))),
+                _ => {}
+            }
+        }
+        lifetime
+    }
+
+    pub fn record(&mut self, game: GameRecord) -> Result<(), String> {//记入一局并保存
+        add(&mut self.games, &game.table);
+        self.food += game.food.max(0) as u64;
+        self.stars += game.stars.max(0) as u64;
+        self.millis += game.millis.max(0);
+        self.ticks += game.ticks as u64;
+        self.longest = self.longest.max(game.length);
+        if let Some(cause) = game.death {
+            add(&mut self.deaths, &cause);
+        }
+        self.save()
+    }
+
+    pub fn total_games(&self) -> u32 {
+        self.games.iter().map(|(_, count)| count).sum()
+    }
+
+    pub fn average_millis(&self) -> i64 {//平均每局存活的时间
+        self.millis / self.total_games().max(1) as i64
+    }
+
+    pub fn average_ticks(&self) -> u64 {
+        self.ticks / self.total_games().max(1) as u64
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut text = format!("food\t{}\nstars\t{}\nmillis\t{}\nticks\t{}\nlongest\t{}\n", self.food, self.stars, self.millis, self.ticks, self.longest);
+        for (table, count) in self.games.iter() {
+            text += &format!("games\t{}\t{}\n", table, count);
+        }
+        for (cause, count) in self.deaths.iter() {
+            text += &format!("deaths\t{}\t{}\n", cause, count);
+        }
+        fs::write(&self.path, text).map_err(|e| format!("无法保存统计：{}", e))
+    }
+
+    pub fn to_json(&self) -> Json {
+        let counts = |counts: &Vec<(String, u32)>| Json::Object(counts.iter().map(|(key, count)| (key.clone(), Json::Number(*count as f64))).collect());
+        Json::Object(vec![
+            (String::from("games"), Json::Number(self.total_games() as f64)),
+            (String::from("games_by_level"), counts(&self.games)),
+            (String::from("food"), Json::Number(self.food as f64)),
+            (String::from("stars"), Json::Number(self.stars as f64)),
+            (String::from("play_seconds"), Json::Number(self.millis as f64 / 1000.0)),
+            (String::from("longest"), Json::Number(self.longest as f64)),
+            (String::from("deaths"), counts(&self.deaths)),
+            (String::from("average_seconds"), Json::Number(self.average_millis() as f64 / 1000.0)),
+            (String::from("average_ticks"), Json::Number(self.average_ticks() as f64))
+        ])
+    }
+
+    pub fn to_csv(&self) -> String {//每行一项:类别,名字,数值
+        let mut text = String::from("category,name,value\n");
+        text += &format!("total,games,{}\n", self.total_games());
+        text += &format!("total,food,{}\n", self.food);
+        text += &format!("total,stars,{}\n", self.stars);
+        text += &format!("total,play_seconds,{:.3}\n", self.millis as f64 / 1000.0);
+        text += &format!("total,longest,{}\n", self.longest);
+        text += &format!("average,seconds,{:.3}\n", self.average_millis() as f64 / 1000.0);
+        text += &format!("average,ticks,{}\n", self.average_ticks());
+        for (table, count) in self.games.iter() {
+            text += &format!("games,{},{}\n", csv_field(table), count);
+        }
+        for (cause, count) in self.deaths.iter() {
+            text += &format!("deaths,{},{}\n", csv_field(cause), count);
+        }
+        text
+    }
+}
+
+fn csv_field(text: &str) -> String {//自定义地图和物品的名字可能包含逗号或引号
+    if text.contains(',') || text.contains('"') {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn table_name(table: &str) -> String {//排行榜的写法换成关卡的名字
+    let (coop, rest) = if table.starts_with("coop-") { ("合作", &table[5..]) } else { ("", table) };
+    let (prefix, rest) = match (rest.starts_with("time"), rest.find('-')) { //限时玩法,例如time60-level-1
+        (true, Some(i)) if rest[4..i].parse::<u32>().is_ok() => (format!("{}限时{}秒", coop, &rest[4..i]), &rest[i + 1..]),
+        _ if rest.starts_with("survival-") => (format!("{}生存", coop), &rest[9..]),
+        _ if rest.starts_with("zen-") => (format!("{}休闲", coop), &rest[4..]),
+        _ => (coop.to_string(), rest)
+    };
+    if rest == "daily" {
+        format!("{}每日挑战", prefix)
+    } else if rest.starts_with("level-") {
+        format!("{}第{}关", prefix, &rest[6..])
+    } else if rest.starts_with("map-") {
+        format!("{}{}", prefix, &rest[4..])
+    } else {
+        format!("{}{}", prefix, rest)
+    }
+}
+
+fn death_name(cause: &str) -> String {//与cause_key对应
+    match cause {
+        "border" => String::from("撞到四周的隔墙"),
+        "wall" => String::from("撞到地图中的墙"),
+        "self" => String::from("咬到自己"),
+        "crash" => String::from("撞到其他的蛇"),
+        _ if cause.starts_with("item:") => format!("吃到{}", &cause[5..]),
+        _ => cause.to_string()
+    }
+}
+
+fn draw_counts(title: &str, counts: &[(String, u32)], names: fn(&str) -> String, row: i32, rows: usize) {//分两列输出各项的次数
+    move_cursor(4, row);
+    addstr(title);
+    for (i, (key, count)) in counts.iter().take(rows * 2).enumerate() {
+        move_cursor(6 + (i / rows) as i32 * 24, row + 1 + (i % rows) as i32);
+        addstr(&fit(&format!("{} {}", names(key), count), 22));
+    }
+    if counts.is_empty() {
+        move_cursor(6, row + 1);
+        addstr("无");
+    }
+}
+
+pub fn run_stats(data: &GameData) {//在地图区域显示累计的统计,可以导出成JSON或CSV
+    set_cursor_visiable(false);
+    let lifetime = &data.lifetime;
+    clear_screen();
+    move_cursor(4, 2);
+    set_color(data.colors.yellow);//调成黄色
+    addstr("游戏统计");
+    set_color(data.colors.white);//调成白色
+    let seconds = lifetime.millis / 1000;
+    move_cursor(4, 4);
+    addstr(&fit(&format!("总局数：{}  总用时：{}小时{}分{}秒", lifetime.total_games(), seconds / 3600, seconds / 60 % 60, seconds % 60), 48));
+    move_cursor(4, 5);
+    addstr(&fit(&format!("吃到食物：{}  吃到星星：{}", lifetime.food, lifetime.stars), 48));
+    move_cursor(4, 6);
+    addstr(&fit(&format!("最长的蛇：{}  平均存活：{:.1}秒({}步)", lifetime.longest,
+        lifetime.average_millis() as f64 / 1000.0, lifetime.average_ticks()), 48));
+    draw_counts("各关局数：", &lifetime.games, table_name, 8, 5);
+    draw_counts("死亡原因：", &lifetime.deaths, death_name, 14, 4);
+    move_cursor(4, 21);
+    set_color(data.colors.yellow);//调成黄色
+    addstr("J：导出JSON  C：导出CSV  Esc：返回");
+    refresh().unwrap();
+    cbreak().unwrap();
+    loop {
+        let json = match getch() {
+            Ok(CharacterResult::Character('j')) | Ok(CharacterResult::Character('J')) => true,
+            Ok(CharacterResult::Character('c')) | Ok(CharacterResult::Character('C')) => false,
+            Ok(CharacterResult::Character('\u{001B}')) | Ok(CharacterResult::Character('q')) | Ok(CharacterResult::Key(KeyBinding::Exit)) => break,
+            _ => continue
+        };
+        set_color(data.colors.white);//调成白色
+        let path = read_line(4, 22, "导出到：");
+        set_cursor_visiable(false);
+        move_cursor(4, 22);
+        addstr(&fit("", 48));
+        if path.is_empty() {
+            continue;
+        }
+        let text = if json { format!("{}\n", data.lifetime.to_json()) } else { data.lifetime.to_csv() };
+        move_cursor(4, 22);
+        match fs::write(&path, text) {
+            Ok(_) => addstr(&fit(&format!("已导出到{}", path), 48)),
+            Err(e) => {
+                set_color(data.colors.red);//调成红色
+                addstr(&fit(&format!("无法导出：{}", e), 48));
+            }
+        }
+        refresh().unwrap();
+    }
+    clear_screen();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(table: &str, death: Option<&str>) -> GameRecord {
+        GameRecord { table: table.to_string(), food: 3, stars: 1, millis: 2500, ticks: 40, length: 9, death: death.map(String::from) }
+    }
+
+    #[test]
+    fn saves_and_loads_totals() {
+        let path = std::env::temp_dir().join(format!("snake-rust-lifetime-{}.txt", std::process::id()));
+        let mut lifetime = Lifetime::load(path.clone());
+        assert_eq!(lifetime.total_games(), 0);
+        lifetime.record(game("level-1", Some("self"))).unwrap();
+        lifetime.record(game("level-1", None)).unwrap();
+        lifetime.record(game("map-a,b", Some("item:barrier"))).unwrap();
+        let loaded = Lifetime::load(path.clone());
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.games, vec![(String::from("level-1"), 2), (String::from("map-a,b"), 1)]);
+        assert_eq!(loaded.deaths, vec![(String::from("self"), 1), (String::from("item:barrier"), 1)]);
+        assert_eq!((loaded.food, loaded.stars, loaded.millis, loaded.ticks, loaded.longest), (9, 3, 7500, 120, 9));
+        assert_eq!((loaded.average_millis(), loaded.average_ticks()), (2500, 40));
+        assert_eq!(loaded.to_csv(), lifetime.to_csv());
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("level-1"), "level-1");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn names_score_tables() {
+        assert_eq!(table_name("level-3"), "第3关");
+        assert_eq!(table_name("coop-level-2"), "合作第2关");
+        assert_eq!(table_name("time60-level-1"), "限时60秒第1关");
+        assert_eq!(table_name("coop-time120-map-迷宫"), "合作限时120秒迷宫");
+        assert_eq!(table_name("survival-map-time-1"), "生存time-1");
+        assert_eq!(table_name("zen-level-6"), "休闲第6关");
+        assert_eq!(table_name("daily"), "每日挑战");
+        assert_eq!(table_name("map-timeless"), "timeless");
+    }
+}
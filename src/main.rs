@@ -1,7 +1,27 @@
 extern crate ncursesw;
 
+mod level;
+mod editor;
+mod rules;
+mod items;
+mod scheduler;
+mod score;
+mod ai;
+mod attract;
+mod simulate;
+mod json;
+mod env;
+mod sync;
+mod net;
+mod watch;
+mod replay;
+#[cfg(unix)]
+mod cast; //录制需要伪终端,只支持类Unix系统
+mod export;
+mod lifetime;
+mod daily;
+
 use core::convert::TryFrom;
-use ncursesw::shims::bindings::printw;
 use std::io::Read;
 use ncursesw::*;
 use ncursesw::normal::*;
@@ -11,8 +31,19 @@ use std::thread;
 use std::os::raw::c_int;
 use std::os::raw::c_char;
 use std::ffi::CString;
-use rand::Rng;
+use std::path::{Path, PathBuf};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use level::{Level, MAX_LENGTH, MAX_VELOCITY, MIN_VELOCITY};
+use rules::{GameMode, Ruleset, Variant, WallRule};
+use items::{Effect, ItemKind};
+use scheduler::Scheduler;
+use score::{HighScores, ScoreEntry};
+use ai::{AgentKind, GameView};
+use replay::Replay;
+use lifetime::{GameRecord, Lifetime};
+use daily::{DailyBoard, DailyRun};
 
+#[derive(Default)]
 struct ColorPairs {
     red: ColorPair,
     yellow: ColorPair,
@@ -21,6 +52,50 @@ struct ColorPairs {
     green: ColorPair
 }
 
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum ColorName {
+    Red,
+    Yellow,
+    Cyan,
+    White,
+    Green
+}
+
+impl ColorName {
+    fn parse(name: &str) -> Option<ColorName> {
+        match name {
+            "red" => Some(ColorName::Red),
+            "yellow" => Some(ColorName::Yellow),
+            "cyan" => Some(ColorName::Cyan),
+            "white" => Some(ColorName::White),
+            "green" => Some(ColorName::Green),
+            _ => None
+        }
+    }
+
+    fn key(&self) -> &'static str {//与parse对应的名字
+        match self {
+            ColorName::Red => "red",
+            ColorName::Yellow => "yellow",
+            ColorName::Cyan => "cyan",
+            ColorName::White => "white",
+            ColorName::Green => "green"
+        }
+    }
+}
+
+impl ColorPairs {
+    fn get(&self, name: ColorName) -> ColorPair {
+        match name {
+            ColorName::Red => self.red,
+            ColorName::Yellow => self.yellow,
+            ColorName::Cyan => self.cyan,
+            ColorName::White => self.white,
+            ColorName::Green => self.green
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 enum Direction {
     Left,
@@ -47,6 +122,16 @@ impl TryFrom<&KeyBinding> for Direction {
     }
 }
 
+fn wasd_direction(ch: char) -> Option<Direction> {//玩家2使用的WASD键
+    match ch.to_ascii_lowercase() {
+        'w' => Some(Direction::Up),
+        's' => Some(Direction::Down),
+        'a' => Some(Direction::Left),
+        'd' => Some(Direction::Right),
+        _ => None
+    }
+}
+
 impl Into_int for Direction {
     fn to_int(&self) -> i16 {
         match self {
@@ -65,28 +150,114 @@ struct SnakeData {
     direction: Direction//行走方向
 }
 
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Controller {
+    Arrows, //方向键
+    Wasd, //WASD键
+    Agent(AgentKind), //由Agent控制
+    Remote //由网络上的玩家控制
+}
+
+#[derive(Clone)]
+struct Snake {
+    body: [SnakeData; 81], //蛇身,第0节是蛇头,最后一节记录上一步的蛇尾
+    length: i16, //蛇身长度,初始为4节
+    stats: usize, //生命值和得分记录在GameData::stats中的位置
+    head_color: ColorName, //蛇头和蛇尾的颜色
+    body_color: ColorName, //蛇身的颜色
+    controller: Controller, //控制这条蛇的按键或Agent
+    turns: Vec<Direction>, //还没有生效的转向
+    player: bool, //是否是玩家的蛇,电脑对手为false
+    alive: bool
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Cause {
+    Border, //四周的隔墙
+    Wall, //地图中的墙
+    Bite, //咬到自己
+    Crash, //撞到其他的蛇
+    Item(usize) //吃到会扣生命值的物品
+}
+
+#[derive(Clone)]
+struct Stats {
+    hp: i8, //生命值
+    food: i16, //用来记录所吃到的食物数
+    score: i32, //得分
+    combo: i32, //连击倍数,1表示没有连击
+    combo_tick: Option<u32>, //上一次吃到食物的步数,还没吃到时为None
+    last_hit: Option<Cause>, //最后一次扣生命值的原因
+    stars: i16, //吃到的能恢复生命值的物品数
+    history: Vec<HpChange> //每一次生命值的变化,结算时输出
+}
+
+#[derive(Debug, Copy, Clone)]
+struct HpChange {
+    tick: u32, //发生在第几步
+    change: i8, //负数表示受伤
+    hp: i8, //变化后的生命值
+    cause: Cause
+}
+
+impl Stats {
+    fn new(hp: i8) -> Stats {
+        Stats { hp: hp, food: 0, score: 0, combo: 1, combo_tick: None, last_hit: None, stars: 0, history: Vec::new() }
+    }
+
+    fn hurt(&mut self, amount: i8, cause: Cause, tick: u32) {//生命值减少,受伤时连击中断
+        self.hp = self.hp.saturating_sub(amount);
+        self.last_hit = Some(cause);
+        self.combo = 1;
+        self.history.push(HpChange { tick: tick, change: -amount, hp: self.hp, cause: cause });
+    }
+
+    fn heal(&mut self, amount: i8, cause: Cause, tick: u32) {//生命值增加,最多加到6
+        if self.hp < 6 {
+            let hp = self.hp.saturating_add(amount).min(6);
+            self.history.push(HpChange { tick: tick, change: hp - self.hp, hp: hp, cause: cause });
+            self.hp = hp;
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 enum ItemType {
     None,
-    Food,
-    Star,
-    Barrier
+    Wall,
+    Item(usize) //物品在物品表中的位置
 }
 
 struct GameData {
-    length: i16, //n用来记录蛇身长度,初始为4节
     velocity: i32,//用来给记录蛇的移动速度
     t1: i64, //用来记录用时
     t2: i64, //用来记录用时
-    t3: i64, //用来记录用时
     level: i8,//用来记录关卡
-    hp: i8, //记录蛇的生命值,初始化为6
-    food: i16, //用来记录所吃到的食物数
-    food_x: i16, //记录食物所在地
-    food_y: i16, //记录食物所在地
+    start_hp: i8, //开局时蛇的生命值,初始化为6
+    tick: u32, //游戏已经进行的步数
     colors: ColorPairs,
-    snake_infos: [SnakeData; 81],
-    map: [[ItemType; 22]; 26]
+    snakes: Vec<Snake>, //场上所有的蛇,第0条由玩家1控制
+    stats: Vec<Stats>, //每个玩家的生命值和得分
+    mode: GameMode, //单人、双人对战或双人合作
+    bots: usize, //电脑控制的蛇的数量
+    bot_agent: AgentKind, //电脑对手使用的策略
+    autopilot: Option<AgentKind>, //玩家1交给Agent控制时使用的策略
+    map: [[ItemType; 22]; 26],
+    expires: [[u32; 22]; 26], //地图上物品消失的步数,0表示不会消失
+    items: Vec<ItemKind>, //物品表
+    scheduler: Scheduler, //按步数安排物品出现
+    high_scores: HighScores, //排行榜
+    lifetime: Lifetime, //所有游戏累计的统计
+    daily_board: DailyBoard, //每日挑战的成绩和连续天数
+    daily: Option<DailyRun>, //正在进行的每日挑战
+    stage: Level, //当前关卡的地图和设置
+    rules: Ruleset, //游戏规则
+    seed: u64, //随机数种子,同样的种子和操作得到同样的游戏
+    rng: XorShiftRng, //物品出现的位置等都使用这个随机数生成器
+    back_to_menu: bool, //是否按了Esc键返回选关
+    test_play: bool, //在地图编辑器中试玩,不计入排行榜和统计
+    spectators: Option<watch::Publisher>, //使用--broadcast时把游戏发给观众
+    replay: Option<Replay> //正在录制的回放
 }
 
 fn move_cursor(x: i32, y: i32) {
@@ -110,16 +281,35 @@ fn addstr(str: &str) {
     ncursesw::addstr(str).unwrap();
 }
 
+fn fit(text: &str, width: usize) -> String {//按终端显示宽度截断或补齐字符串，中文占两列
+    let mut result = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = if c.is_ascii() { 1 } else { 2 };
+        if used + w > width {
+            break;
+        }
+        result.push(c);
+        used += w;
+    }
+    result + &" ".repeat(width - used)
+}
+
 fn getchar() -> u8 {
     let mut buffer = [0; 10];
     std::io::stdin().read(&mut buffer).expect("Failed on getchar");
     buffer[0]
 }
 
-fn print_i32(value: i32) {
-    unsafe {
-        printw(CString::new("%d").unwrap().as_ptr(), value);
-    }
+fn data_file(name: &str) -> PathBuf {//保存排行榜等数据的文件,位于~/.snake-rust目录下
+    data_dir().join(name)
+}
+
+fn data_dir() -> PathBuf {
+    let mut path = PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| String::from(".")));
+    path.push(".snake-rust");
+    let _ = std::fs::create_dir_all(&path);
+    path
 }
 
 fn timestamp() -> i64 {
@@ -142,6 +332,90 @@ fn main() {
         const LC_ALL: i32 = 0;
         setlocale(LC_ALL, CString::new("").unwrap().as_ptr());
     }
+    let items = match items::load_items("items.conf") {
+        Ok(items) => items,
+        Err(message) => {
+            eprintln!("items.conf：{}", message);
+            return;
+        }
+    };
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() >= 2 && args[1] == "simulate" { //不使用终端,批量模拟游戏
+        if let Err(message) = simulate::run_simulate(&args[2..], items) {
+            eprintln!("{}", message);
+        }
+        return;
+    }
+    if args.len() >= 2 && args[1] == "serve" { //联网对战的服务器,不使用终端
+        if let Err(message) = net::run_serve(&args[2..], items) {
+            eprintln!("{}", message);
+        }
+        return;
+    }
+    #[cfg(unix)]
+    {
+        if args.len() >= 2 && args[1] == "cast" { //把回放转换成asciicast,不使用终端
+            if let Err(message) = cast::run_convert(&args[2..]) {
+                eprintln!("{}", message);
+            }
+            return;
+        }
+    }
+    if args.len() >= 2 && args[1] == "export" { //把回放导出成SVG动画或PNG图片,不使用终端
+        if let Err(message) = export::run_export(&args[2..], items) {
+            eprintln!("{}", message);
+        }
+        return;
+    }
+    if args.len() >= 2 && args[1] == "env" { //通过标准输入输出与强化学习程序交互
+        if let Err(message) = env::run_env(&args[2..], items) {
+            eprintln!("{}", message);
+        }
+        return;
+    }
+    let mut autopilot = None;
+    let mut spectators = None;
+    let mut command = &args[1..];
+    while !command.is_empty() && command[0].starts_with("--") {
+        if command.len() < 2 {
+            eprintln!("{}缺少参数", command[0]);
+            return;
+        }
+        match command[0].as_str() {
+            "--agent" => { //玩家1交给Agent控制
+                autopilot = match AgentKind::parse(&command[1]) {
+                    Some(kind) => Some(kind),
+                    None => {
+                        eprintln!("未知的Agent：{}(可选random、greedy、bfs、astar、hamilton)", command[1]);
+                        return;
+                    }
+                };
+            },
+            #[cfg(unix)]
+            "--record" => { //在伪终端中运行去掉这个参数的命令,录制成asciicast
+                let at = args.len() - command.len();
+                let rest: Vec<String> = args[1..at].iter().chain(args[at + 2..].iter()).cloned().collect();
+                if let Err(message) = cast::run_record(&command[1], &rest) {
+                    eprintln!("{}", message);
+                }
+                return;
+            },
+            "--broadcast" => { //允许其他终端用watch观看
+                spectators = match watch::Publisher::bind(&command[1]) {
+                    Ok(publisher) => Some(publisher),
+                    Err(message) => {
+                        eprintln!("{}", message);
+                        return;
+                    }
+                };
+            },
+            _ => {
+                eprintln!("未知的参数：{}", command[0]);
+                return;
+            }
+        }
+        command = &command[2..];
+    }
     initscr().unwrap();
 
     if !has_colors() {
@@ -154,38 +428,109 @@ fn main() {
     use_default_colors().unwrap();
     noecho().unwrap();
 
-    let mut global_data = GameData {
-        length: 4,
-        velocity: 0,
-        t1: 0,
-        t2: 0,
-        t3: 0,
-        level: 1,
-        hp: 6,
-        food: 0,
-        food_x: 0,
-        food_y: 0,
-        colors: ColorPairs {
-            red: ColorPair::new(1, Colors::new(Color::Dark(BaseColor::Red), Color::Dark(BaseColor::Black))).unwrap(),
-            yellow: ColorPair::new(2, Colors::new(Color::Dark(BaseColor::Yellow), Color::Dark(BaseColor::Black))).unwrap(),
-            cyan: ColorPair::new(3, Colors::new(Color::Dark(BaseColor::Cyan), Color::Dark(BaseColor::Black))).unwrap(),
-            white: ColorPair::new(4, Colors::new(Color::Dark(BaseColor::White), Color::Dark(BaseColor::Black))).unwrap(),
-            green: ColorPair::new(5, Colors::new(Color::Dark(BaseColor::Green), Color::Dark(BaseColor::Black))).unwrap()
-        },
-        snake_infos: [SnakeData { x: 0, y: 0, direction: Direction::Up }; 81],
-        map: [[ItemType::None; 22]; 26]
-    };
+    let mut global_data = new_game(items, ColorPairs {
+        red: ColorPair::new(1, Colors::new(Color::Dark(BaseColor::Red), Color::Dark(BaseColor::Black))).unwrap(),
+        yellow: ColorPair::new(2, Colors::new(Color::Dark(BaseColor::Yellow), Color::Dark(BaseColor::Black))).unwrap(),
+        cyan: ColorPair::new(3, Colors::new(Color::Dark(BaseColor::Cyan), Color::Dark(BaseColor::Black))).unwrap(),
+        white: ColorPair::new(4, Colors::new(Color::Dark(BaseColor::White), Color::Dark(BaseColor::Black))).unwrap(),
+        green: ColorPair::new(5, Colors::new(Color::Dark(BaseColor::Green), Color::Dark(BaseColor::Black))).unwrap()
+    });
+    global_data.autopilot = autopilot;
+    global_data.spectators = spectators;
+    global_data.seed = timestamp() as u64;
+    global_data.rng = seed_rng(global_data.seed);
+
+    if command.len() == 2 && command[0] == "edit" { //直接进入地图编辑器
+        editor::run_editor(&mut global_data, &command[1]);
+        endwin().unwrap();
+        return;
+    }
+    if command.len() >= 2 && command[0] == "join" { //连接到联网对战的服务器
+        let result = net::run_join(&mut global_data, &command[1..]);
+        endwin().unwrap();
+        if let Err(message) = result {
+            eprintln!("{}", message);
+        }
+        return;
+    }
+    if !command.is_empty() && command[0] == "replay" { //观看回放,默认是上一局
+        let autoplay = command.get(1).map(String::as_str) == Some("--autoplay"); //转换asciicast时使用
+        let path = command.get(if autoplay { 2 } else { 1 }).cloned().unwrap_or_else(|| data_file(LAST_REPLAY).to_string_lossy().into_owned());
+        let result = Replay::load(&path).map(|replay| replay::run_viewer(&mut global_data, &replay, autoplay));
+        if !autoplay { //录制用的伪终端不用恢复,保留最后一帧
+            endwin().unwrap();
+        }
+        if let Err(message) = result {
+            eprintln!("{}", message);
+        }
+        return;
+    }
+    if command.len() == 2 && command[0] == "watch" { //观看其他终端中正在进行的游戏
+        let result = watch::run_watch(&mut global_data, &command[1]);
+        endwin().unwrap();
+        if let Err(message) = result {
+            eprintln!("{}", message);
+        }
+        return;
+    }
 
     start_animation(&mut global_data);
     loop {
         select_level(&mut global_data);//用来选择关卡并根据关卡设置蛇的移动速度
         set_cursor_visiable(false);//隐藏光标
-        if !begin_game(&mut global_data) {
-            break;    //游戏结束
+        while begin_game(&mut global_data) { //结算界面选择再来一局
+            start_stage(&mut global_data);
+            set_cursor_visiable(false);//隐藏光标
         }
     }
+}
+
+fn new_game(items: Vec<ItemKind>, colors: ColorPairs) -> GameData {//创建游戏数据,不使用终端时颜色可以是默认值
+    new_game_in(&data_dir(), items, colors)
+}
+
+#[cfg(test)]
+fn test_game(items: Vec<ItemKind>) -> GameData {//测试用的游戏数据,排行榜和统计指向不存在的目录,不读写~/.snake-rust
+    let dir = std::env::temp_dir().join(format!("snake-rust-test-{}", std::process::id()));
+    new_game_in(&dir, items, ColorPairs::default())
+}
+
+fn new_game_in(dir: &Path, items: Vec<ItemKind>, colors: ColorPairs) -> GameData {//排行榜和统计使用dir目录下的文件
+    GameData {
+        velocity: 0,
+        t1: 0,
+        t2: 0,
+        level: 1,
+        start_hp: 6,
+        tick: 0,
+        colors: colors,
+        snakes: Vec::new(),
+        stats: Vec::new(),
+        mode: GameMode::Single,
+        bots: 0,
+        bot_agent: AgentKind::Greedy,
+        autopilot: None,
+        map: [[ItemType::None; 22]; 26],
+        expires: [[0; 22]; 26],
+        scheduler: Scheduler::new(&items),
+        high_scores: HighScores::load(dir.join("highscores.txt")),
+        lifetime: Lifetime::load(dir.join("lifetime.txt")),
+        daily_board: DailyBoard::load(dir.join("daily.txt")),
+        daily: None,
+        items: items,
+        stage: Level::builtin(1),
+        rules: Ruleset::classic(),
+        seed: 1,
+        rng: seed_rng(1),
+        back_to_menu: false,
+        test_play: false,
+        spectators: None,
+        replay: None
+    }
+}
 
-    endwin().unwrap();
+fn seed_rng(seed: u64) -> XorShiftRng {//由种子创建随机数生成器
+    XorShiftRng::from_seed([seed as u32, (seed >> 32) as u32, 0x9E37_79B9, 0x7F4A_7C15])
 }
 
 fn start_animation(data: &mut GameData) {//绘制启动画面以及隔墙
@@ -294,66 +639,228 @@ fn start_animation(data: &mut GameData) {//绘制启动画面以及隔墙
     }
     move_cursor(56, 11);
     addstr("* * * * * * * * * * * *");                                          //56
+    draw_sidebar(data);
+}
+
+fn draw_frame(data: &mut GameData) {//不带动画地绘制隔墙和右侧信息栏
+    clear().unwrap();
+    set_color(data.colors.cyan);//调整输出颜色
+    for x in 0..40 {
+        move_cursor_add_char(0, 2 * x, '*'); //输出第一行
+        move_cursor_add_char(23, 2 * x, '*'); // 输出最下面一行
+    }
+    for y in 1..23 {
+        move_cursor_add_char(y, 0, '*'); //输出第一列
+        move_cursor_add_char(y, 54, '*'); //输出中间那列
+        move_cursor_add_char(y, 78, '*'); //输出最右边列
+    }
+    move_cursor(56, 11);
+    addstr("* * * * * * * * * * * *");
+    draw_sidebar(data);
+}
+
+fn draw_sidebar(data: &mut GameData) {//输出右侧的提示文字
     move_cursor(19, 0);
     set_color(data.colors.yellow);//调整输出颜色
     addstr("| | |贪 吃 蛇| | |"); //输出标题
+    let versus = data.mode == GameMode::Versus;
+    move_cursor(56, 1);
+    addstr(&fit(match data.mode {
+        GameMode::Single => "",
+        GameMode::Versus => "玩家1：方向键",
+        GameMode::Coop => "两人共享生命值和得分"
+    }, 22));
     move_cursor(56, 2);
-    addstr("已用时间：");
+    addstr(if data.rules.time_left(0).is_some() { "剩余时间：" } else { "已用时间：" });
     move_cursor(75, 2);
     addstr("秒");
-    move_cursor(56, 4);
-    addstr("生命值：");
-    move_cursor(56, 6);
-    addstr("当前长度：");
-    move_cursor(56, 8);
-    addstr("已吃食物：");
+    draw_panel_labels(3);
+    if data.rules.harmless() { //休闲玩法不显示生命值
+        move_cursor(56, 3);
+        addstr(&fit("", 22));
+    }
+    move_cursor(56, 9);
+    addstr(&fit(if versus { "" } else { "最高分：" }, 22));
     move_cursor(56, 10);
     addstr("第             关");
-    move_cursor(64, 12);
-    addstr("提示：");
-    move_cursor(56, 13);
-    addstr("向上：↑   向上：←");
-    move_cursor(56, 14);
-    addstr("向下：↓   向右：→");
-    move_cursor(56, 15);
-    addstr("暂停/开始：确定键 ");
-    move_cursor(56, 16);
-    addstr("重新选关 ：Esc键");
-    move_cursor(64, 18);
-    addstr("注意！");
+    let hints = match data.mode { //双人对战时下半部分输出玩家2的信息
+        GameMode::Single => ["        提示：", "向上：↑   向上：←", "向下：↓   向右：→", "暂停/开始：确定键", "重新选关 ：Esc键", "", "        注意！"],
+        GameMode::Versus => ["玩家2：WASD键", "", "", "", "", "", ""],
+        GameMode::Coop => ["        提示：", "玩家1：方向键", "玩家2：WASD键", "暂停/开始：确定键", "重新选关 ：Esc键", "", "        注意！"]
+    };
+    for i in 0..hints.len() {
+        move_cursor(56, 12 + i as i32);
+        addstr(&fit(hints[i], 22));
+    }
+    if versus {
+        draw_panel_labels(13);
+    }
+    if data.rules.harmless() {
+        let hints = ["1:可以自由穿墙", "2:咬到自己会断掉尾巴", "+/-：加速/减速"];
+        for i in 0..hints.len() {
+            move_cursor(56, 19 + i as i32);
+            addstr(&fit(hints[i], 22));
+        }
+    } else {
+        draw_rule_hint(data);
+        move_cursor(56, 21);
+        addstr("2:吃到小星星生命值加一");
+    }
+    refresh().unwrap();
+}
+
+fn draw_panel_labels(row: i32) {//从row行开始输出一条蛇的状态栏文字
+    let labels = ["生命值：", "当前长度：", "已吃食物：", "连击：", "", "得分："];
+    for i in 0..labels.len() {
+        move_cursor(56, row + i as i32);
+        addstr(&fit(labels[i], 22));
+    }
+}
+
+fn draw_rule_hint(data: &GameData) {//根据当前的撞墙规则输出提示
+    let wall = data.rules.wall_for(&data.stage);
     move_cursor(56, 19);
+    set_color(data.colors.yellow);//调整输出颜色
     addstr("1:撞到");
     set_color(data.colors.red);
     addstr("*");
     set_color(data.colors.yellow);//调整输出颜色
-    addstr("或墙生命值减一");
-    move_cursor(56, 21);
-    addstr("2:吃到小星星生命值加一");
-    refresh().unwrap();
+    move_cursor(56, 20);
+    addstr(&fit("", 22));
+    move_cursor(63, 19);
+    match wall {
+        WallRule::WrapPenalty => {
+            addstr(&fit("或墙生命值减一", 15));
+        },
+        WallRule::Solid => {
+            addstr(&fit("生命值减一", 15));
+            move_cursor(58, 20);
+            addstr("撞到墙游戏结束");
+        },
+        WallRule::FreeWrap => {
+            addstr(&fit("生命值减一", 15));
+            move_cursor(58, 20);
+            addstr("可以自由穿墙");
+        }
+    }
 }
 
 fn select_level(data: &mut GameData)//用来选择关卡并根据关卡设置蛇的移动速度
 {
     set_cursor_visiable(true);//显示光标
-    data.length = 4; //n用来记录蛇身长度,初始为3节
-    data.hp = 6; //记录蛇的生命值,初始化为6
-    data.snake_infos[0].x = 6;                  //
-    data.snake_infos[0].y = 10;                 //
-    data.snake_infos[0].direction = Direction::Right;      //
-    data.snake_infos[1].x = 4;                 //
-    data.snake_infos[1].y = 10;                //     初始化蛇所在位置和移动方向
-    data.snake_infos[1].direction = Direction::Right;     //
-    data.snake_infos[2].x = 2;                 //
-    data.snake_infos[2].y = 10;                //
-    data.snake_infos[2].direction = Direction::Right;    //
-    data.snake_infos[3].x = 4; ////////////////
-    data.snake_infos[3].y = 4; ///////////////记录蛇尾的信息
-    data.snake_infos[3].direction = Direction::Right; ////
+    data.start_hp = 6; //记录蛇的生命值,初始化为6
+    if let Some(run) = data.daily.take() { //每日挑战结束,恢复原来的设置
+        run.restore(data);
+    }
     loop {
+        move_cursor(15, 7);
+        set_color(data.colors.yellow);//变成黄色
+        addstr("E：编辑地图   L：载入地图   R：回放上一局");
+        move_cursor(15, 8);
+        addstr(&format!("W：撞墙规则({})", data.rules.wall.name()));
+        move_cursor(15, 9);
+        addstr(&fit(&format!("M：游戏模式({})", data.mode.name()), 30));
+        move_cursor(15, 10);
+        addstr(&fit(&format!("A：电脑对手({}条)", data.bots), 30));
+        move_cursor(15, 11);
+        addstr(&fit(&format!("D：电脑策略({})", data.bot_agent.name()), 30));
+        move_cursor(15, 12);
+        addstr(&fit(&format!("P：玩家1({})", data.autopilot.map_or("键盘", |kind| kind.name())), 30));
+        move_cursor(15, 13);
+        addstr(&fit(&format!("T：玩法({})   S：游戏统计", data.rules.variant.name()), 38));
+        move_cursor(15, 14);
+        addstr("C：每日挑战");
         move_cursor(15, 3);
         addstr("请输入关数(1-6)：");
         refresh().unwrap();
-        data.level = getchar() as i8 - 48;
+        let key = match wait_menu_key() {
+            Some(key) => key,
+            None => { //空闲太久,进入演示
+                attract::run_attract(data);
+                continue;
+            }
+        };
+        if key == b'w' || key == b'W' { //切换撞墙规则
+            data.rules.wall = data.rules.wall.next();
+            continue;
+        }
+        if key == b'm' || key == b'M' { //切换单人或双人对战
+            data.mode = data.mode.next();
+            if data.mode == GameMode::Versus {
+                data.rules.variant = Variant::Classic;
+            }
+            continue;
+        }
+        if key == b't' || key == b'T' { //切换玩法,双人对战只有经典玩法
+            if data.mode != GameMode::Versus {
+                data.rules.variant = data.rules.variant.next();
+            }
+            continue;
+        }
+        if key == b'a' || key == b'A' { //增加电脑控制的蛇,超过3条时归零
+            data.bots = (data.bots + 1) % (MAX_BOTS + 1);
+            continue;
+        }
+        if key == b'd' || key == b'D' {
+            data.bot_agent = data.bot_agent.next();
+            continue;
+        }
+        if key == b'p' || key == b'P' { //玩家1在键盘和各种Agent之间切换
+            data.autopilot = match data.autopilot {
+                None => Some(AgentKind::Random),
+                Some(AgentKind::Hamilton) => None,
+                Some(kind) => Some(kind.next())
+            };
+            continue;
+        }
+        if key == b'r' || key == b'R' { //观看上一局的回放
+            match Replay::load(&data_file(LAST_REPLAY).to_string_lossy()) {
+                Ok(last) => replay::run_viewer(data, &last, false),
+                Err(_) => {
+                    move_cursor(15, 5);
+                    set_color(data.colors.red);//变成红色
+                    addstr("还没有可以回放的游戏");
+                }
+            }
+            continue;
+        }
+        if key == b'c' || key == b'C' { //所有人同一天玩同样的地图
+            if daily::run_menu(data) {
+                break;
+            }
+            continue;
+        }
+        if key == b's' || key == b'S' { //查看累计的统计
+            lifetime::run_stats(data);
+            continue;
+        }
+        if key == b'e' || key == b'E' {
+            let path = read_line(15, 5, "地图文件：");
+            if !path.is_empty() {
+                editor::run_editor(data, &path);
+                draw_frame(data);
+            }
+            clear_prompt();
+            continue;
+        }
+        if key == b'l' || key == b'L' {
+            let path = read_line(15, 5, "地图文件：");
+            match Level::load(&path) {
+                Ok(stage) => {
+                    data.stage = stage;
+                    data.level = 0;
+                    break;
+                },
+                Err(message) => {
+                    clear_prompt();
+                    move_cursor(15, 5);
+                    set_color(data.colors.red);//变成红色
+                    addstr(&message);
+                    continue;
+                }
+            }
+        }
+        data.level = key as i8 - 48;
         if data.level == 0 { //判断是否作弊
             move_cursor(15, 3);
             set_color(data.colors.red);//变成红色
@@ -362,7 +869,7 @@ fn select_level(data: &mut GameData)//用来选择关卡并根据关卡设置蛇
             set_color(data.colors.yellow);//变成黄色
             addstr("请输入你想要的蛇的生命值：");
             refresh().unwrap();
-            data.hp = getchar() as i8 - 48;
+            data.start_hp = getchar() as i8 - 48;
             move_cursor(15, 3);
             addstr("                      ");
             move_cursor(15, 5);
@@ -370,46 +877,236 @@ fn select_level(data: &mut GameData)//用来选择关卡并根据关卡设置蛇
             continue;//返回选关处
         }
         if data.level < 7 && data.level > 0 {
+            data.stage = Level::builtin(data.level);
             break;    //判断关数是否溢出
         }
+        clear_prompt();
         move_cursor(15, 5);
         addstr("输入错误！");
-        move_cursor(32, 3);
-        addstr("          ");
         refresh().unwrap();
     }
-    move_cursor(15, 3);
-    addstr("                   ");
-    match data.level {
-        1 => {
-            data.velocity = 600;    //
-        },
-        2 => {
-            data.velocity = 400;    //
-        },
-        3 => {
-            data.velocity = 200;    //    根据关数来设定蛇的移动速度
+    clear_prompt();
+    start_stage(data);
+}
+
+fn start_stage(data: &mut GameData) {//用新的种子开始选好的关卡
+    data.seed = match data.daily.as_ref() { //每一局使用新的种子,每日挑战使用当天的种子
+        Some(run) => run.challenge.seed,
+        None => timestamp() as u64
+    };
+    load_stage(data);
+    clear_screen();//清除屏幕
+    draw_map(data);
+    draw_sidebar(data); //单人和双人对战的信息栏不同
+}
+
+fn wait_menu_key() -> Option<u8> {//等待选关界面的按键,空闲超过一定时间时返回None
+    let idle = timestamp();
+    cbreak().unwrap();
+    let key = loop {
+        timeout(Duration::from_secs(1)).unwrap();
+        match getch() {
+            Ok(CharacterResult::Character(ch)) if ch.is_ascii() => break Some(ch as u8),
+            Ok(CharacterResult::Key(KeyBinding::Enter)) => break Some(b'\n'),
+            Ok(_) => break Some(0),
+            Err(_) => {
+                if timestamp() - idle >= attract::IDLE_SECONDS * 1000 {
+                    break None;
+                }
+            }
         }
-        4 => {
-            data.velocity = 150;    //
+    };
+    nodelay(stdscr(), false).unwrap();
+    key
+}
+
+fn clear_prompt() {//清除选关时的提示文字
+    for y in 3..15 {
+        move_cursor(15, y);
+        addstr("                                      ");
+    }
+}
+
+fn read_line(x: i32, y: i32, prompt: &str) -> String {//在指定位置读取一行输入
+    let mut text = String::new();
+    cbreak().unwrap();
+    set_cursor_visiable(true);//显示光标
+    loop {
+        move_cursor(x, y);
+        addstr(prompt);
+        addstr(&text);
+        addstr(" ");
+        move_cursor(x + prompt.chars().count() as i32 * 2 + text.len() as i32, y);
+        refresh().unwrap();
+        match getch() {
+            Ok(CharacterResult::Character('\n')) | Ok(CharacterResult::Key(KeyBinding::Enter)) => break,
+            Ok(CharacterResult::Character('\u{001B}')) => { //Esc键取消输入
+                text.clear();
+                break;
+            },
+            Ok(CharacterResult::Character('\u{007F}')) | Ok(CharacterResult::Key(KeyBinding::Backspace)) => {
+                text.pop();
+            },
+            Ok(CharacterResult::Character(ch)) if !ch.is_control() && text.len() < 20 => text.push(ch),
+            _ => {}
         }
-        5 => {
-            data.velocity = 100;    //
-        },
-        6 => {
-            data.velocity = 60;    //
+    }
+    text
+}
+
+fn load_stage(data: &mut GameData) {//根据关卡数据初始化地图和蛇的位置
+    data.rng = seed_rng(data.seed); //同样的种子得到同样的一局
+    data.tick = 0;
+    data.velocity = data.stage.velocity;
+    data.map = data.stage.cells;
+    if data.rules.harmless() { //休闲玩法去掉地图中预先放置的障碍物
+        for x in 0..26 {
+            for y in 0..22 {
+                if let ItemType::Item(kind) = data.map[x][y] {
+                    if data.items[kind].damages() {
+                        data.map[x][y] = ItemType::None;
+                    }
+                }
+            }
         }
-        _ => {
-            panic!();
+    }
+    data.expires = [[0; 22]; 26];
+    data.scheduler = Scheduler::new(&data.items);
+    data.snakes.clear();
+    data.stats.clear();
+    data.stats.push(Stats::new(data.start_hp));
+    data.snakes.push(new_snake(data.stage.spawn, 0, Controller::Arrows, ColorName::Red, ColorName::Yellow));
+    if data.mode != GameMode::Single { //玩家2用WASD键控制绿色的蛇,合作时和玩家1共用生命值和得分
+        let stats = if data.mode == GameMode::Coop { 0 } else { 1 };
+        if stats == data.stats.len() {
+            data.stats.push(Stats::new(data.start_hp));
+        }
+        data.snakes.push(new_snake(data.stage.second_spawn(), stats, Controller::Wasd, ColorName::Green, ColorName::Cyan));
+    }
+    for _ in 0..data.bots {
+        add_bot(data);
+    }
+    if let Some(kind) = data.autopilot { //玩家1交给Agent控制
+        data.snakes[0].controller = Controller::Agent(kind);
+    }
+}
+
+const MAX_BOTS: usize = 3; //电脑控制的蛇最多的数量
+
+fn add_bot(data: &mut GameData) {//电脑控制的蛇有自己的生命值,和玩家争抢食物
+    let spawn = free_spawn(data);
+    let stats = data.stats.len();
+    data.stats.push(Stats::new(6));
+    let mut snake = new_snake(spawn, stats, Controller::Agent(data.bot_agent), ColorName::White, ColorName::Cyan);
+    snake.player = false;
+    data.snakes.push(snake);
+}
+
+fn free_spawn(data: &mut GameData) -> (usize, usize, Direction) {//为电脑控制的蛇找一个前后都空着并远离其他蛇的出生点
+    let directions = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+    for _ in 0..200 {
+        let x = data.rng.gen_range(0, 26);
+        let y = data.rng.gen_range(0, 22);
+        let direction = directions[data.rng.gen_range(0, 4)];
+        let (dx, dy) = match direction {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0)
+        };
+        let clear = (-3..4).all(|k| { //身后3格放蛇身,前方3格留出空地
+            let cx = (x as i32 + dx * k).rem_euclid(26) as usize;
+            let cy = (y as i32 + dy * k).rem_euclid(22) as usize;
+            data.map[cx][cy] == ItemType::None && data.snakes.iter().all(|snake| {
+                snake.body[..snake.length as usize].iter().all(|body| {
+                    let (bx, by) = snake_cell(body);
+                    (bx as i32 - cx as i32).abs() + (by as i32 - cy as i32).abs() >= 3
+                })
+            })
+        });
+        if clear {
+            return (x, y, direction);
+        }
+    }
+    data.stage.second_spawn()
+}
+
+fn new_snake(spawn: (usize, usize, Direction), stats: usize, controller: Controller, head_color: ColorName, body_color: ColorName) -> Snake {
+    let (x, y, direction) = spawn;
+    let mut head = SnakeData { x: 2 * (x as i16 + 1), y: y as i16 + 1, direction: direction };
+    let mut snake = Snake {
+        body: [head; 81],
+        length: 4, //初始为4节
+        stats: stats,
+        head_color: head_color,
+        body_color: body_color,
+        controller: controller,
+        turns: Vec::new(),
+        player: true,
+        alive: true
+    };
+    for i in 0..snake.length as usize { //初始化蛇所在位置和移动方向，蛇身依次排在蛇头后面
+        snake.body[i] = head;
+        match direction {
+            Direction::Up => head.y += 1,
+            Direction::Down => head.y -= 1,
+            Direction::Left => head.x += 2,
+            Direction::Right => head.x -= 2
         }
+        head.x = (head.x + 50) % 52 + 2; //超出边界的蛇身绕到另一侧
+        head.y = (head.y + 21) % 22 + 1;
+    }
+    snake
+}
+
+fn draw_map(data: &mut GameData) {//输出地图上的墙和障碍物
+    for x in 0..26 {
+        for y in 0..22 {
+            draw_cell(data, x, y);
+        }
+    }
+    refresh().unwrap();
+}
+
+fn draw_cell(data: &GameData, x: usize, y: usize) {//输出地图上某一格的内容
+    move_cursor(2 * (x as i32 + 1), y as i32 + 1);
+    draw_item(data, data.map[x][y]);
+}
+
+fn draw_item(data: &GameData, item: ItemType) {//在光标处输出墙或物品
+    match item {
+        ItemType::Wall => {
+            set_color(data.colors.cyan);
+            addstr("*");
+        },
+        ItemType::Item(kind) => {
+            set_color(data.colors.get(data.items[kind].color));
+            addstr(&data.items[kind].glyph);
+        },
+        ItemType::None => addstr(" ")
     }
-    clear_screen(data);//清除屏幕
 }
-fn update_data(data: &mut GameData)//用来记录和判断游戏的各种状态数据
+fn update_data(data: &mut GameData)//用来输出游戏的各种状态数据
 {
     move_cursor(66, 2);
     set_color(data.colors.red);//调成红色
-    print_i32((data.t1 / 1000) as i32); //程序已用时间
+    match data.rules.time_left(data.t1) {
+        Some(left) => addstr(&format!("{:<8}", (left + 999) / 1000)), //限时玩法倒计时
+        None => addstr(&format!("{:<8}", data.t1 / 1000)) //程序已用时间
+    }
+    draw_level(data);
+    update_panel(data, 0, 3);
+    if data.mode == GameMode::Versus {
+        update_panel(data, 1, 13);
+    } else {
+        move_cursor(66, 9);
+        set_color(data.colors.red);//调成红色
+        addstr(&format!("{:<8}", data.high_scores.best(&score_table(data)).max(data.stats[0].score))); //输出本关的最高分
+    }
+    refresh().unwrap();
+}
+
+fn draw_level(data: &GameData) {//输出关卡栏,当前关卡显示为红色
     match data.level {
         1 => {
             move_cursor(59, 10);
@@ -462,147 +1159,236 @@ fn update_data(data: &mut GameData)//用来记录和判断游戏的各种状态
             addstr("6");
         }
         _ => {
-            panic!();
-        }
-    }
-    match data.hp {
-        1 => {
-            move_cursor(65, 4);
-            set_color(data.colors.green);//调成绿色
-            addstr("▁");
-            set_color(data.colors.red);//调成红色
-            addstr("▂▃▅▆▇");
-        },
-        2 => {
-            move_cursor(65, 4);
-            set_color(data.colors.green);//调成绿色
-            addstr("▁▂");
-            set_color(data.colors.red);//调成红色
-            addstr("▃▅▆▇");
-        },
-        3 => {
-            move_cursor(65, 4);
-            set_color(data.colors.green);//调成绿色
-            addstr("▁▂▃");
-            set_color(data.colors.red);//调成红色
-            addstr("▅▆▇");
-        },
-        4 => {
-            move_cursor(65, 4);
-            set_color(data.colors.green);//调成绿色
-            addstr("▁▂▃▅");
-            set_color(data.colors.red);//调成红色
-            addstr("▆▇");
-        },
-        5 => {
-            move_cursor(65, 4);
-            set_color(data.colors.green);//调成绿色
-            addstr("▁▂▃▅▆");
+            move_cursor(59, 10);
             set_color(data.colors.red);//调成红色
-            addstr("▇");
-        },
-        6 => {
-            move_cursor(65, 4);
-            set_color(data.colors.green);//调成绿色
-            addstr("▁▂▃▅▆▇");
-        },
-        _ => {
-            move_cursor(65, 4);
-            set_color(data.colors.green);//调成绿色
-            addstr("！超级模式 ！");
+            addstr(&fit(&data.stage.name, 12)); //自定义地图输出地图名
         }
     }
-    move_cursor(66, 6);
+}
+
+fn team_length(data: &GameData, stats: usize) -> i16 {//共用同一份生命值和得分的蛇的长度之和
+    data.snakes.iter().filter(|snake| snake.stats == stats).map(|snake| snake.length - 1).sum()
+}
+
+fn update_panel(data: &GameData, player: usize, row: i32) {//从row行开始输出一条蛇的生命值、长度、食物、连击和得分
+    let snake = &data.snakes[player];
+    let stats = &data.stats[snake.stats];
+    if !data.rules.harmless() {
+        draw_hp(data, stats.hp, row);
+    }
+    move_cursor(66, row + 1);
     set_color(data.colors.red);//调成红色
-    print_i32(data.length as i32 - 1); //输出蛇的当前长度
-    move_cursor(66, 8);
-    print_i32(data.food as i32); //输出蛇当前已经吃到食物
-    refresh().unwrap();
+    addstr(&format!("{:<8}", team_length(data, snake.stats))); //输出蛇的当前长度
+    move_cursor(66, row + 2);
+    addstr(&format!("{:<8}", stats.food)); //输出蛇当前已经吃到食物
+    move_cursor(66, row + 3);
+    if stats.combo > 1 { //输出连击倍数和剩余时间
+        addstr(&format!("x{:<7}", stats.combo));
+    } else {
+        addstr("        ");
+    }
+    let left = stats.combo_tick.map_or(0, |tick| (tick + score::COMBO_TICKS).saturating_sub(data.tick));
+    let bar = if stats.combo > 1 { (left * 20 / score::COMBO_TICKS) as usize } else { 0 };
+    move_cursor(56, row + 4);
+    set_color(data.colors.green);//调成绿色
+    addstr(&format!("[{}{}]", "=".repeat(bar), " ".repeat(20 - bar)));
+    set_color(data.colors.red);//调成红色
+    move_cursor(66, row + 5);
+    addstr(&format!("{:<8}", stats.score)); //输出当前得分
+}
+
+fn draw_hp(data: &GameData, hp: i8, row: i32) {//输出生命值条,超过6时为超级模式
+    const BARS: [&str; 6] = ["▁", "▂", "▃", "▅", "▆", "▇"];
+    move_cursor(65, row);
+    set_color(data.colors.green);//调成绿色
+    if hp > 6 {
+        addstr("！超级模式 ！");
+        return;
+    }
+    let hp = hp.max(0) as usize;
+    addstr(&BARS[..hp].concat());
+    set_color(data.colors.red);//调成红色
+    addstr(&BARS[hp..].concat());
+    addstr("       "); //清除超级模式留下的文字
 }
-fn clear_screen(data: &mut GameData)//用来清除屏幕
+
+fn clear_screen()//用来清除屏幕
 {
     for i in 2..23 {
         move_cursor(2, i);
         addstr("                                                    ");
     }
-    data.map[data.food_x as usize][data.food_y as usize]  = ItemType::None;
     refresh().unwrap();
 }
 
-fn update_ui(data: &mut GameData)//用来随机产生障碍物以及食物和生命药水以及用来判断游戏的各种参数
+fn update_ui(data: &mut GameData)//用来随机产生障碍物以及食物和生命药水,判断蛇是否吃到食物
 {
-    let mut a: usize;
-    let mut b: usize;
-    let mut e: usize;
-    let mut f: usize; //a，b用来表示小星星的坐标   c，d代表障碍物坐标
-    if data.map[data.food_x as usize][data.food_y as usize] == ItemType::None { //判断食物是不是被吃掉
-        loop {
-            data.food_x = rand::thread_rng().gen_range(0, 26); //产生随机横坐标
-            data.food_y = rand::thread_rng().gen_range(0, 22); //产生随机纵坐标
-            if data.map[data.food_x as usize][data.food_y as usize] == ItemType::None {
-                break;    //当此处无其他元素是才生效
-            }
+    for kind in 0..data.items.len() { //保持地图上食物等物品的数量
+        if data.rules.harmless() && data.items[kind].damages() {
+            continue;
+        }
+        for _ in count_items(data, kind)..data.items[kind].keep {
+            spawn_item(data, kind);
+        }
+    }
+    let difficulty = data.rules.difficulty(data.tick);
+    for kind in data.scheduler.due(data.tick, &data.items, difficulty, &mut data.rng) { //随机出现障碍物和星星等物品
+        if data.rules.harmless() && data.items[kind].damages() { //休闲玩法没有障碍物
+            continue;
+        }
+        let max = if data.items[kind].damages() { rules::harder(data.items[kind].max, difficulty) } else { data.items[kind].max };
+        if max == 0 || count_items(data, kind) < max {
+            spawn_item(data, kind);
+        }
+    }
+    for i in 0..data.snakes.len() {
+        if !data.snakes[i].alive {
+            continue;
         }
-        data.map[data.food_x as usize][data.food_y as usize] = ItemType::Food; //随机出现食物
-        move_cursor((2 * (data.food_x + 1)).into(), (data.food_y + 1).into()); //定位到食物出现的位置
-        set_color(data.colors.yellow);//调成黄色
-        addstr("●"); //打印出食物
-    }
-    if data.t1 / 20 > 0 && data.t1 % 12 == 0 && data.t1 > data.t3
-        && data.map[(data.snake_infos[0].x as usize - 1) / 2][data.snake_infos[0].y as usize - 1] == ItemType::None {
-        loop {
-            e = rand::thread_rng().gen_range(0, 26); //产生随机横坐标
-            f = rand::thread_rng().gen_range(0, 22); //产生随机纵坐标
-            if data.map[e][f] == ItemType::None {
-                break;    //当此处无其他元素是才生效
+        for k in 0..data.snakes[i].length as usize {
+            let (x, y) = snake_cell(&data.snakes[i].body[k]);
+            if let ItemType::Item(kind) = data.map[x][y] {
+                if data.items[kind].grows() { //判断蛇是否吃到食物
+                    collect_item(data, i, x, y);
+                    break;
+                }
             }
         }
-        move_cursor(2 * (e as i32 + 1), f as i32 + 1); //定位到障碍物出现的位置
-        data.map[e][f] = ItemType::Barrier; //随机出现障碍物
-        set_color(data.colors.red);//调成红色
-        addstr("*"); //打印出障碍物
-        data.t3 = data.t1; //以免产生多个障碍物
-        if data.hp < 7 {
-            move_cursor(18, 24);
-            set_color(data.colors.white);//调成白色
-            addstr("温馨提示：在选关的时候输入0可以开启作弊模式");
+    }
+    for x in 0..26 {
+        for y in 0..22 {
+            if data.expires[x][y] > 0 && data.tick >= data.expires[x][y] { //物品存在时间已到
+                data.map[x][y] = ItemType::None;
+                data.expires[x][y] = 0;
+            }
         }
     }
-    if data.t1 / 25 > 0 && data.t1 % 15 == 0 && data.t1 > data.t3
-        && data.map[(data.snake_infos[0].x as usize - 1) / 2][data.snake_infos[0].y as usize - 1] == ItemType::None { //减少星星出现的几率
-        loop {
-            a = rand::thread_rng().gen_range(0, 26); //产生随机横坐标
-            b = rand::thread_rng().gen_range(0, 22); //产生随机纵坐标
-            if data.map[a][b] == ItemType::None {
-                break;    //当此处无其他元素是才生效
+}
+
+const BLINK_TICKS: u32 = 12; //物品消失前闪烁的步数
+const MAX_TURNS: usize = 3; //每条蛇最多缓存的转向次数
+
+fn draw_board(data: &GameData) {//输出地图、物品和所有的蛇
+    for x in 0..26 {
+        for y in 0..22 {
+            let left = data.expires[x][y].saturating_sub(data.tick);
+            if data.expires[x][y] > 0 && left <= BLINK_TICKS && left % 2 == 1 { //快要消失的物品闪烁提示
+                move_cursor(2 * (x as i32 + 1), y as i32 + 1);
+                addstr(" ");
+            } else {
+                draw_cell(data, x, y);
             }
         }
-        data.map[a][b] = ItemType::Star; //随机出现小星星（吃到星星长度减1）
-        move_cursor(2 * (a as i32 + 1), b as i32 + 1); //定位到星星出现的位置（吃到星星长度减1）
-        set_color(data.colors.green);//调成绿色
-        addstr("☆"); //打印出星星（吃到星星长度减1）
-        data.t3 = data.t1; //以免产生多个障碍物
-        if data.hp < 7 {
-            move_cursor(18, 24);
+    }
+    for snake in data.snakes.iter().filter(|snake| snake.alive) {
+        draw_snake(data, snake);
+    }
+    refresh().unwrap();
+}
+
+fn draw_tip(data: &GameData) {//地图上有伤害物品时提示作弊模式
+    if data.start_hp < 7 {
+        move_cursor(18, 24);
+        set_color(data.colors.white);//调成白色
+        if (0..data.items.len()).any(|kind| data.items[kind].damages() && count_items(data, kind) > 0) {
+            addstr("温馨提示：在选关的时候输入0可以开启作弊模式");
+        } else {
             addstr("                                            ");
         }
     }
-    for i in 0..data.length as usize {
-        if data.map[(data.snake_infos[i].x as usize - 1) / 2][data.snake_infos[i].y as usize - 1] == ItemType::Food { //判断蛇是否吃到食物
-            data.length += 1;//让蛇长度加1
-            data.food += 1;//将食物数加1
-            data.map[(data.snake_infos[i].x as usize - 1) / 2][data.snake_infos[i].y as usize - 1] = ItemType::None; //让食物标示归零
-            break;
+    refresh().unwrap();
+}
+
+fn draw_snake(data: &GameData, snake: &Snake) {//输出一条蛇,最后一节只用来记录上一步的蛇尾
+    let length = snake.length as usize;
+    set_color(data.colors.get(snake.body_color));
+    for i in 1..length - 1 {
+        move_cursor(snake.body[i].x.into(), snake.body[i].y.into()); //转到当前蛇身位置
+        addstr("●"); //打印蛇身
+    }
+    set_color(data.colors.get(snake.head_color));
+    move_cursor(snake.body[length - 2].x.into(), snake.body[length - 2].y.into()); //转到当前蛇尾位置
+    addstr("●"); //打印蛇尾
+    move_cursor(snake.body[0].x.into(), snake.body[0].y.into()); //转到蛇头位置
+    addstr("◆"); //打印蛇头
+}
+
+fn snake_cell(snake: &SnakeData) -> (usize, usize) {//蛇身所在的地图格子
+    ((snake.x as usize - 1) / 2, snake.y as usize - 1)
+}
+
+fn count_items(data: &GameData, kind: usize) -> u32 {//地图上某种物品的数量
+    let mut count = 0;
+    for column in data.map.iter() {
+        count += column.iter().filter(|item| **item == ItemType::Item(kind)).count() as u32;
+    }
+    count
+}
+
+fn spawn_item(data: &mut GameData, kind: usize) {//在随机的空地上放置物品
+    for _ in 0..100 {
+        let x = data.rng.gen_range(0, 26); //产生随机横坐标
+        let y = data.rng.gen_range(0, 22); //产生随机纵坐标
+        if data.map[x][y] == ItemType::None { //当此处无其他元素是才生效
+            data.map[x][y] = ItemType::Item(kind);
+            data.expires[x][y] = if data.items[kind].lifetime > 0 { data.tick + data.items[kind].lifetime } else { 0 };
+            return;
+        }
+    }
+}
+
+fn collect_item(data: &mut GameData, snake: usize, x: usize, y: usize) {//第snake条蛇吃到地图上的物品
+    let kind = match data.map[x][y] {
+        ItemType::Item(kind) => kind,
+        _ => return
+    };
+    data.map[x][y] = ItemType::None; //让物品标示归零
+    data.expires[x][y] = 0;
+    let player = data.snakes[snake].stats;
+    let length = data.snakes[snake].length;
+    let now = data.tick;
+    let stats = &mut data.stats[player];
+    if data.items[kind].grows() { //在时间窗口内接连吃到食物时连击倍数增加
+        if stats.combo_tick.map_or(false, |tick| now <= tick + score::COMBO_TICKS) {
+            stats.combo = (stats.combo + 1).min(score::MAX_COMBO);
+        } else {
+            stats.combo = 1;
         }
+        stats.combo_tick = Some(data.tick);
     }
-    if data.map[(data.snake_infos[0].x as usize - 1) / 2][data.snake_infos[0].y as usize - 1] == ItemType::Star { //判断蛇是否吃到星星
-        data.map[(data.snake_infos[0].x as usize - 1) / 2][data.snake_infos[0].y as usize - 1] = ItemType::None; //让星星标示归零
-        if data.hp < 6 {
-            data.hp += 1;    //将生命值加1
+    for effect in data.items[kind].effects.iter() {
+        match effect {
+            Effect::Grow(n) => stats.score += score::food_points(data.velocity, length - 1, *n) * stats.combo,
+            Effect::Heal(_) => stats.score += score::STAR_BONUS,
+            _ => {}
+        }
+    }
+    if data.items[kind].grows() {
+        stats.food += 1;//将食物数加1
+    }
+    if data.items[kind].heals() {
+        stats.stars += 1;
+    }
+    for effect in data.items[kind].effects.clone() {
+        match effect {
+            Effect::Grow(n) => {
+                let snake = &mut data.snakes[snake];
+                let length = (snake.length + n).min(MAX_LENGTH);
+                for i in snake.length as usize..length as usize { //新长出的蛇身先和蛇尾重合
+                    snake.body[i] = snake.body[snake.length as usize - 1];
+                }
+                snake.length = length;
+            },
+            Effect::Shrink(n) => {
+                let snake = &mut data.snakes[snake];
+                snake.length = (snake.length - n).max(4);
+            },
+            Effect::Heal(n) => data.stats[player].heal(n, Cause::Item(kind), data.tick), //将生命值加1
+            Effect::Damage(n) => data.stats[player].hurt(n, Cause::Item(kind), data.tick), //将生命值减一
+            Effect::Speed(n) => data.velocity = data.velocity.saturating_add(n).max(MIN_VELOCITY).min(MAX_VELOCITY),
+            Effect::Score(n) => data.stats[player].score += n
         }
     }
-    data.t1 = timestamp() - data.t2; //刷新游戏运行时间
-    refresh().unwrap();
 }
 
 fn get_key() -> Result<KeyBinding, ()>
@@ -628,137 +1414,683 @@ fn get_key() -> Result<KeyBinding, ()>
     };
 }
 
-fn handle_key_event(data: &mut GameData)//用户是否操作键盘
+fn handle_key_event(data: &mut GameData)//读取这一步内的所有按键,方向键交给对应的蛇
 {
     halfdelay(Duration::from_secs(10)).expect("halfdelay failed!");
-    let key = match get_key() {
-        Ok(result) => {
-            result
+    let mut keys = Vec::new();
+    if let Ok(key) = getch() { //最多等待0.1秒
+        keys.push(key);
+        cbreak().unwrap();
+        nodelay(stdscr(), true).unwrap();
+        while let Ok(key) = getch() { //两个玩家可能同时按键,取出所有已经按下的键
+            keys.push(key);
+        }
+        nodelay(stdscr(), false).unwrap();
+    }
+    for key in keys {
+        let (controller, direction) = match key {
+            CharacterResult::Character('\n') | CharacterResult::Key(KeyBinding::Enter) => { //判断用户是否暂停
+                pause_game(data);
+                continue;
+            },
+            CharacterResult::Character('\u{001B}') | CharacterResult::Key(KeyBinding::Exit) => { //判断是否重新选关
+                data.back_to_menu = true;
+                return;
+            },
+            CharacterResult::Key(key) => match Direction::try_from(&key) {
+                Ok(direction) => (Controller::Arrows, direction),
+                Err(_) => continue
+            },
+            CharacterResult::Character(ch @ '+') | CharacterResult::Character(ch @ '=') | CharacterResult::Character(ch @ '-')
+                if data.rules.variant == Variant::Zen => { //休闲玩法随时调整速度
+                change_speed(data, if ch == '-' { rules::SPEED_STEP } else { -rules::SPEED_STEP });
+                continue;
+            },
+            CharacterResult::Character(ch) => match wasd_direction(ch) {
+                Some(direction) => (Controller::Wasd, direction),
+                None => continue
+            }
+        };
+        for (i, snake) in data.snakes.iter_mut().enumerate().filter(|(_, snake)| snake.controller == controller) {
+            if snake.turns.len() < MAX_TURNS {
+                snake.turns.push(direction);
+                if let Some(replay) = data.replay.as_mut() { //记录到回放中
+                    replay.turns.push((data.tick, i, direction));
+                }
+            }
+        }
+    }
+}
+
+fn change_speed(data: &mut GameData, change: i32) {//改变蛇的移动间隔,记录到回放中
+    data.velocity = (data.velocity + change).max(MIN_VELOCITY).min(MAX_VELOCITY);
+    if let Some(replay) = data.replay.as_mut() {
+        replay.speeds.push((data.tick, data.velocity));
+    }
+}
+
+fn pause_game(data: &mut GameData) {//暂停游戏直到再次按下确定键
+    let a: i64;
+    let b: i64;
+    a = timestamp(); //记录当前程序已用时间
+    move_cursor(20, 1);
+    set_color(data.colors.white);//调成白色
+    addstr("已暂停,按确定键开始");
+    loop {
+        match get_key() {
+            Ok(key) => {
+                if key == KeyBinding::Enter {////判断是否按键且是否解除暂停
+                    move_cursor(20, 1);
+                    addstr("                     "); //清除"已暂停,按确定键开始"这行字
+                    break;
+                }
+            },
+            Err(_) => continue
+        }
+    }
+    b = timestamp(); //记录当前程序已用时间
+    data.t2 += b - a; //将暂停加到t2上供t1减去
+}
+
+fn score_table(data: &GameData) -> String {//当前关卡使用的排行榜,合作模式单独排名
+    if data.daily.is_some() { //每日挑战的成绩另外保存
+        return String::from("daily");
+    }
+    let mut prefix = String::from(if data.mode == GameMode::Coop { "coop-" } else { "" });
+    match data.rules.variant { //限时和生存玩法单独排名
+        Variant::TimeAttack(seconds) => prefix += &format!("time{}-", seconds),
+        Variant::Survival => prefix += "survival-",
+        Variant::Zen => prefix += "zen-",
+        Variant::Classic => {}
+    }
+    if data.stage.number > 0 {
+        format!("{}level-{}", prefix, data.stage.number)
+    } else {
+        format!("{}map-{}", prefix, data.stage.name)
+    }
+}
+
+fn record_score(data: &mut GameData) {//得分进入排行榜时记录名字,然后输出排行榜
+    let key = score_table(data);
+    let stats = data.stats[0].clone();
+    set_color(data.colors.white);//调成白色
+    if data.high_scores.qualifies(&key, stats.score) {
+        flushinp().unwrap();
+        let name = read_line(20, 7, "新纪录！请输入名字：");
+        let entry = ScoreEntry {
+            name: if name.is_empty() { String::from("无名氏") } else { name },
+            score: stats.score,
+            length: team_length(data, 0),
+            food: stats.food,
+            seconds: data.t1 / 1000
+        };
+        if let Err(message) = data.high_scores.insert(&key, entry) {
+            move_cursor(20, 7);
+            addstr(&fit(&message, 32));
+        }
+    }
+    clear_screen();
+    set_color(data.colors.yellow);//调成黄色
+    move_cursor(20, 4);
+    addstr(&format!("排行榜 - {}", data.stage.name));
+    let table = data.high_scores.table(&key);
+    for i in 0..table.len() {
+        move_cursor(12, 6 + i as i32);
+        addstr(&format!("{:>2}. {}{:>8}分  长度{:<4}用时{}秒", i + 1, fit(&table[i].name, 12),
+            table[i].score, table[i].length, table[i].seconds));
+    }
+    refresh().unwrap();
+    thread::sleep(Duration::from_secs(3));//延时
+    clear_screen();
+}
+
+fn hit_wall(data: &mut GameData, snake: usize, cause: Cause) {//根据撞墙规则处理蛇撞墙
+    let wall = data.rules.wall_for(&data.stage);
+    let tick = data.tick;
+    let stats = &mut data.stats[data.snakes[snake].stats];
+    match wall {
+        WallRule::WrapPenalty => stats.hurt(1, cause, tick), //将生命值减一
+        WallRule::Solid => { //撞墙即死亡
+            let hp = stats.hp;
+            stats.hurt(hp, cause, tick);
         },
-        Err(_) => return
-    };
-    match Direction::try_from(&key) {
-        Ok(direction) => {
-            if (direction.to_int() + data.snake_infos[0].direction.to_int() != Direction::Up.to_int() + Direction::Down.to_int())
-                && (direction.to_int() + data.snake_infos[0].direction.to_int() != Direction::Left.to_int() + Direction::Right.to_int())
-                && direction != data.snake_infos[0].direction { //判断按键是否是方向键，并且是不是蛇移动方向的反方向
-                data.snake_infos[0].direction = direction;    //如果不是就改变蛇头方向
+        WallRule::FreeWrap => {}
+    }
+}
+
+fn move_snake(data: &mut GameData, i: usize) {//按方向移动一条蛇,穿过四周的隔墙时按撞墙规则处理
+    let snake = &mut data.snakes[i];
+    for k in (1..snake.length as usize).rev() {
+        snake.body[k] = snake.body[k - 1];    //移动蛇
+    }
+    while !snake.turns.is_empty() {
+        let direction = snake.turns.remove(0);
+        let current = snake.body[0].direction;
+        if (direction.to_int() + current.to_int() != Direction::Up.to_int() + Direction::Down.to_int())
+            && (direction.to_int() + current.to_int() != Direction::Left.to_int() + Direction::Right.to_int())
+            && direction != current { //跳过蛇移动方向的反方向
+            snake.body[0].direction = direction;    //改变蛇头方向
+            break;
+        }
+    }
+    let head = &mut snake.body[0];
+    match head.direction {
+        Direction::Up => head.y -= 1,    //改变蛇头坐标，移动蛇头
+        Direction::Down => head.y += 1,
+        Direction::Left => head.x -= 2,
+        Direction::Right => head.x += 2
+    }
+    let mut crossed = true;
+    if head.x == 0 { //当蛇撞到左墙时
+        head.x = 52; //将其穿墙
+    } else if head.x == 54 { //当蛇撞到右墙时
+        head.x = 2;
+    } else if head.y == 0 { //当蛇撞到上墙时
+        head.y = 22;
+    } else if head.y == 23 { //当蛇撞到下墙时
+        head.y = 1;
+    } else {
+        crossed = false;
+    }
+    if crossed {
+        hit_wall(data, i, Cause::Border);
+    }
+}
+
+fn check_collision(data: &mut GameData, i: usize) {//蛇头撞到自己或其他蛇时死亡,两个蛇头相撞时双方都会死亡,休闲玩法除外
+    let head = data.snakes[i].body[0];
+    for j in 0..data.snakes.len() {
+        let other = &data.snakes[j];
+        if !other.alive || (i != j && data.rules.variant == Variant::Zen) { //休闲玩法撞到其他蛇时直接穿过
+            continue;
+        }
+        let start = if i == j { 1 } else { 0 }; //自己的蛇头不算
+        for k in start..other.length as usize - 1 {
+            if other.body[k].x == head.x && other.body[k].y == head.y {
+                if i == j && data.rules.variant == Variant::Zen { //休闲玩法咬到自己时从咬到的地方断开
+                    data.snakes[i].length = k as i16 + 1;
+                    return;
+                }
+                let stats = data.snakes[i].stats;
+                let hp = data.stats[stats].hp;
+                data.stats[stats].hurt(hp, if i == j { Cause::Bite } else { Cause::Crash }, data.tick); //将蛇死亡
+                return;
+            }
+        }
+    }
+}
+
+fn step(data: &mut GameData) {//游戏前进一步:移动所有的蛇,处理碰撞、物品和死亡
+    data.tick += 1;
+    if data.rules.difficulty(data.tick) > 0 && data.tick % rules::DIFFICULTY_TICKS == 0 { //生存玩法每升一级加速一成
+        data.velocity = (data.velocity * 9 / 10).max(rules::SURVIVAL_MIN_VELOCITY).min(data.velocity);
+    }
+    let now = data.tick;
+    for stats in data.stats.iter_mut() {
+        if now % score::SURVIVAL_TICKS == 0 {
+            stats.score += 1; //存活时间加分
+        }
+        if stats.combo > 1 && stats.combo_tick.map_or(true, |tick| now > tick + score::COMBO_TICKS) {
+            stats.combo = 1; //超过时间窗口,连击结束
+        }
+    }
+    for i in 0..data.snakes.len() { //由Agent控制的蛇在移动前决定方向
+        if let Controller::Agent(kind) = data.snakes[i].controller {
+            if data.snakes[i].alive {
+                if let Some(direction) = kind.agent().decide(&GameView::new(data, i)) {
+                    data.snakes[i].turns.push(direction);
+                }
+            }
+        }
+    }
+    for i in 0..data.snakes.len() {
+        if data.snakes[i].alive {
+            move_snake(data, i);
+        }
+    }
+    for i in 0..data.snakes.len() { //所有的蛇都移动后再判断碰撞
+        if data.snakes[i].alive {
+            check_collision(data, i);
+        }
+    }
+    for i in 0..data.snakes.len() {
+        if !data.snakes[i].alive {
+            continue;
+        }
+        let (head_x, head_y) = snake_cell(&data.snakes[i].body[0]);
+        match data.map[head_x][head_y] {
+            ItemType::Item(_) => collect_item(data, i, head_x, head_y), //当蛇头碰到障碍物、星星等物品时
+            ItemType::Wall => hit_wall(data, i, Cause::Wall), //当蛇撞到地图中的墙时
+            ItemType::None => {}
+        }
+    }
+    for snake in data.snakes.iter_mut() {
+        if data.stats[snake.stats].hp <= 0 {
+            snake.alive = false;
+        }
+    }
+    if data.rules.variant == Variant::Survival { //生存玩法的得分就是存活的步数,与回放和模拟的结果相同
+        for snake in data.snakes.iter().filter(|snake| snake.alive) {
+            data.stats[snake.stats].score = data.tick as i32;
+        }
+    }
+    update_ui(data);
+}
+
+fn cause_name(data: &GameData, cause: Cause) -> String {
+    match cause {
+        Cause::Border => String::from("撞到四周的隔墙"),
+        Cause::Wall => String::from("撞到地图中的墙"),
+        Cause::Bite => String::from("咬到自己"),
+        Cause::Crash => String::from("撞到其他的蛇"),
+        Cause::Item(kind) => format!("吃到{}", data.items[kind].name)
+    }
+}
+
+fn cause_key(data: &GameData, cause: Cause) -> String {//统计文件中死亡原因的写法
+    match cause {
+        Cause::Border => String::from("border"),
+        Cause::Wall => String::from("wall"),
+        Cause::Bite => String::from("self"),
+        Cause::Crash => String::from("crash"),
+        Cause::Item(kind) => format!("item:{}", data.items[kind].name)
+    }
+}
+
+fn reached_goal(data: &GameData, snake: &Snake) -> bool {//是否达到关卡目标,合作时按两条蛇的长度之和计算
+    snake.alive && (team_length(data, snake.stats) + 1 >= data.stage.goal_length.min(MAX_LENGTH)
+        || (data.stage.goal_food > 0 && data.stats[snake.stats].food >= data.stage.goal_food))
+}
+
+fn game_outcome(data: &GameData) -> Option<String> {//游戏结束时返回要显示的结果
+    match data.mode {
+        GameMode::Single | GameMode::Coop => { //合作时共用生命值,一条蛇死亡时另一条也会死亡
+            if data.snakes.iter().any(|snake| !snake.alive && snake.player) {
+                Some(String::from("游戏结束！！！"))
+            } else if data.rules.variant == Variant::Zen { //休闲玩法只能按Esc键结束
+                None
+            } else if data.rules.time_left(data.t1) == Some(0) {
+                Some(String::from("时间到！！！"))
+            } else if data.rules.variant == Variant::Classic && reached_goal(data, &data.snakes[0]) {
+                Some(String::from("恭喜你过关！！！"))
+            } else {
+                None
             }
-            return;
         },
-        Err(_) => {}
+        GameMode::Versus => { //先达到目标或者活到最后的玩家获胜
+            if let Some(winner) = data.snakes.iter().position(|snake| snake.player && reached_goal(data, snake)) {
+                return Some(format!("玩家{}获胜！！！", winner + 1));
+            }
+            let alive: Vec<usize> = (0..data.snakes.len()).filter(|i| data.snakes[*i].alive && data.snakes[*i].player).collect();
+            match alive.len() {
+                0 => Some(String::from("平局！！！")),
+                1 => Some(format!("玩家{}获胜！！！", alive[0] + 1)),
+                _ => None
+            }
+        }
+    }
+}
+
+const LAST_REPLAY: &str = "last.replay"; //自动保存的上一局回放
+
+fn save_replay(data: &mut GameData) {//一局结束时保存回放,可以在选关界面按R观看
+    if let Some(mut replay) = data.replay.take() {
+        replay.ticks = data.tick;
+        let _ = replay.save(&data_file(LAST_REPLAY).to_string_lossy());
+    }
+}
+
+fn record_lifetime(data: &mut GameData) {//把玩家1这一局的情况记入累计统计
+    let snake = &data.snakes[0];
+    let stats = &data.stats[snake.stats];
+    let game = GameRecord {
+        table: score_table(data),
+        food: stats.food,
+        stars: stats.stars,
+        millis: data.t1,
+        ticks: data.tick,
+        length: team_length(data, snake.stats),
+        death: if snake.alive { None } else { stats.last_hit.map(|cause| cause_key(data, cause)) }
     };
+    let _ = data.lifetime.record(game); //保存失败不影响游戏
+}
 
-    if key == KeyBinding::Enter { //判断用户是否暂停
-        let a: i64;
-        let b: i64;
-        a = timestamp(); //记录当前程序已用时间
-        move_cursor(20, 1);
-        set_color(data.colors.white);//调成白色
-        addstr("已暂停,按确定键开始");
-        loop {
-            match get_key() {
-                Ok(key) => {
-                    if key == KeyBinding::Enter {////判断是否按键且是否解除暂停
-                        move_cursor(20, 1);
-                        addstr("                     "); //清除"已暂停,按确定键开始"这行字
-                        break;
+fn game_over(data: &mut GameData, message: &str) -> bool {//结算界面:最终状态、生命值变化和致命一击,返回true表示再来一局
+    let mut shown: Vec<(String, usize)> = Vec::new(); //玩家的名字和状态的位置,合作时两人共用一份
+    for (i, snake) in data.snakes.iter().enumerate().filter(|(_, snake)| snake.player) {
+        if !shown.iter().any(|(_, stats)| *stats == snake.stats) {
+            shown.push((if data.mode == GameMode::Versus { format!("玩家{} ", i + 1) } else { String::new() }, snake.stats));
+        }
+    }
+    let mut changes: Vec<(&str, HpChange)> = Vec::new();
+    for (name, stats) in shown.iter() {
+        changes.extend(data.stats[*stats].history.iter().map(|change| (name.as_str(), *change)));
+    }
+    changes.sort_by_key(|(_, change)| change.tick);
+    clear_screen();
+    move_cursor(4, 2);
+    set_color(data.colors.white);//调成白色
+    addstr(message);
+    set_color(data.colors.yellow);//调成黄色
+    for (i, (name, stats)) in shown.iter().enumerate() {
+        move_cursor(4, 4 + i as i32);
+        addstr(&fit(&format!("{}长度：{}  食物：{}  得分：{}", name, team_length(data, *stats),
+            data.stats[*stats].food, data.stats[*stats].score), 48));
+    }
+    move_cursor(4, 7);
+    addstr(&format!("用时：{}秒  共{}步", data.t1 / 1000, data.tick));
+    move_cursor(4, 9);
+    addstr("生命值变化：");
+    const LINES: usize = 8; //最多列出最后几次变化
+    let skipped = changes.len().saturating_sub(LINES);
+    set_color(data.colors.white);//调成白色
+    if changes.is_empty() {
+        move_cursor(6, 10);
+        addstr("没有受伤");
+    }
+    for (i, (name, change)) in changes[skipped..].iter().enumerate() {
+        move_cursor(6, 10 + i as i32);
+        if i == 0 && skipped > 0 {
+            addstr(&format!("……之前还有{}次", skipped + 1));
+            continue;
+        }
+        set_color(if change.change < 0 { data.colors.red } else { data.colors.green });
+        addstr(&fit(&format!("第{:<5}步 {}{} {:+} 剩余{}", change.tick, name, cause_name(data, change.cause), change.change, change.hp), 46));
+    }
+    set_color(data.colors.red);//调成红色
+    let mut row = 18;
+    for (name, stats) in shown.iter().filter(|(_, stats)| data.stats[*stats].hp <= 0) {
+        if let Some(change) = data.stats[*stats].history.last() {
+            move_cursor(4, row);
+            addstr(&fit(&format!("致命一击：{}第{}步 {}", name, change.tick, cause_name(data, change.cause)), 48));
+            row += 1;
+        }
+    }
+    move_cursor(4, 21);
+    set_color(data.colors.yellow);//调成黄色
+    addstr("R：再来一局  Enter：返回选关  S：保存回放");
+    refresh().unwrap();
+    flushinp().unwrap(); //丢掉游戏中多按的键
+    cbreak().unwrap();
+    loop {
+        match getch() {
+            Ok(CharacterResult::Character('r')) | Ok(CharacterResult::Character('R')) => return true,
+            Ok(CharacterResult::Character('\n')) | Ok(CharacterResult::Key(KeyBinding::Enter)) | Ok(CharacterResult::Character('\u{001B}')) => return false,
+            Ok(CharacterResult::Character('s')) | Ok(CharacterResult::Character('S')) => {
+                set_color(data.colors.white);//调成白色
+                let path = read_line(4, 22, "回放文件：");
+                set_cursor_visiable(false);
+                move_cursor(4, 22);
+                addstr(&fit("", 48));
+                move_cursor(4, 22);
+                if path.is_empty() {
+                    continue;
+                }
+                match std::fs::copy(data_file(LAST_REPLAY), &path) {
+                    Ok(_) => addstr(&fit(&format!("已保存到{}", path), 48)),
+                    Err(e) => {
+                        set_color(data.colors.red);//调成红色
+                        addstr(&fit(&format!("无法保存：{}", e), 48));
                     }
-                },
-                Err(_) => continue
-            }
+                }
+                refresh().unwrap();
+            },
+            _ => {}
         }
-        b = timestamp(); //记录当前程序已用时间
-        data.t2 += b - a; //将暂停加到t2上供t1减去
-    } else if key == KeyBinding::Exit { //判断是否重新选关
-        select_level(data);//用来选择关卡并根据关卡设置蛇的移动速度
-        begin_game(data);//开始游戏
     }
 }
-fn begin_game(data: &mut GameData) -> bool
+
+fn begin_game(data: &mut GameData) -> bool //返回true表示用同样的设置再来一局
 {
     set_cursor_visiable(false);
-    let mut n: i16 = 0;
-    //int ch = RIGHT; //向右
     data.t2 = timestamp(); //记录当前程序已用时间
+    data.back_to_menu = false;
+    data.replay = Some(Replay::new(data));
+    watch::publish(data, watch::Publisher::restart);
     loop {
         data.t1 = timestamp() - data.t2; //刷新游戏运行时间
-        update_data(data);//用来记录游戏的各种状态数据
-        move_cursor(data.snake_infos[0].x.into(), data.snake_infos[0].y.into()); //转到蛇头位置
-        set_color(data.colors.red);//改成红色
-        addstr("◆"); //打印蛇头
-        for i in 1..data.length - 1 {
-            move_cursor(data.snake_infos[i as usize].x.into(), data.snake_infos[i as usize].y.into()); //转到当前蛇身位置
-            set_color(data.colors.yellow);//改成黄色
-            addstr("●"); //打印蛇身
-        }
-        move_cursor(data.snake_infos[data.length as usize - 2].x.into(), data.snake_infos[data.length as usize - 2].y.into()); //转到当前蛇尾位置
-        set_color(data.colors.red);//改成红色
-        addstr("●"); //打印蛇尾
+        update_data(data);//用来输出游戏的各种状态数据
+        draw_board(data);
+        draw_tip(data);
         thread::sleep(Duration::from_millis(data.velocity as u64));//控制蛇的移动速度
-        data.t1 = timestamp() - data.t2; //刷新游戏运行时间
-        move_cursor(data.snake_infos[data.length as usize - 1].x.into(), data.snake_infos[data.length as usize - 1].y.into()); //移到蛇尾所在地
-        addstr(" "); //清除上个循环的蛇尾
-        for i in (1..data.length as usize).rev() {
-            data.snake_infos[i] = data.snake_infos[i - 1];    //移动蛇
-        }
         handle_key_event(data);//用户是否操作键盘
-        match data.snake_infos[0].direction {
-            Direction::Up => {
-                data.snake_infos[0].y -= 1;    //改变蛇头坐标，移动蛇头
+        if data.back_to_menu {
+            save_replay(data);
+            if !data.test_play { //中途退出的一局也计入累计统计
+                record_lifetime(data);
             }
-            Direction::Down => {
-                data.snake_infos[0].y += 1;    //改变蛇头坐标，移动蛇头
-            }
-            Direction::Left => {
-                data.snake_infos[0].x -= 2;    //改变蛇头坐标，移动蛇头
+            let _ = daily::record(data); //中途退出的每日挑战也记下成绩
+            watch::publish(data, |publisher, _| publisher.menu());
+            return false;
+        }
+        step(data);
+        data.t1 = timestamp() - data.t2; //刷新游戏运行时间
+        watch::publish(data, watch::Publisher::frame);
+        if let Some(message) = game_outcome(data) {
+            save_replay(data);
+            watch::publish(data, |publisher, _| publisher.over(&message));
+            update_data(data);
+            draw_board(data);
+            if !data.test_play {
+                record_lifetime(data);
             }
-            Direction::Right => {
-                data.snake_infos[0].x += 2;    //改变蛇头坐标，移动蛇头
+            let retry = game_over(data, &message);
+            if data.daily.is_some() {
+                daily::finish(data);
+            } else if data.mode != GameMode::Versus && !data.test_play {
+                record_score(data);
+            } else {
+                clear_screen();
             }
+            watch::publish(data, |publisher, _| publisher.menu());
+            return retry;
         }
-        if data.snake_infos[0].x == 0 { //当蛇撞到左墙时
-            data.hp -= 1;//将生命值减一
-            data.snake_infos[0].x = 52; //将其穿墙
-        }
-        if data.snake_infos[0].x == 54 { //当蛇撞到右墙时
-            data.hp -= 1;//将生命值减一
-            data.snake_infos[0].x = 2; //将其穿墙
-        }
-        if data.snake_infos[0].y == 0 { //当蛇撞到上墙时
-            data.hp -= 1;//将生命值减一
-            data.snake_infos[0].y = 22; //将其穿墙
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::{builtin_items, BARRIER};
+    use crate::level::MAP_WIDTH;
+
+    fn game(mode: GameMode, spawn: (usize, usize, Direction)) -> GameData {//空白地图,只会出现食物
+        let mut data = test_game(builtin_items());
+        for item in data.items.iter_mut() {
+            item.chance = 0;
         }
-        if data.snake_infos[0].y == 23 { //当蛇撞到下墙时
-            data.hp -= 1;//将生命值减一
-            data.snake_infos[0].y = 1; //将其穿墙
+        data.mode = mode;
+        data.stage = Level { spawn: spawn, ..Level::blank() };
+        load_stage(&mut data);
+        data
+    }
+
+    #[test]
+    fn wall_rules_decide_border_crossings() {
+        let cases = [
+            (WallRule::WrapPenalty, None, 5),
+            (WallRule::Solid, None, 0),
+            (WallRule::FreeWrap, None, 6),
+            (WallRule::FreeWrap, Some(WallRule::Solid), 0) //关卡中的设置优先
+        ];
+        for (wall, level_wall, hp) in cases.iter() {
+            let mut data = game(GameMode::Single, (0, 9, Direction::Left));
+            data.rules.wall = *wall;
+            data.stage.wall = *level_wall;
+            step(&mut data);
+            assert_eq!(snake_cell(&data.snakes[0].body[0]), (MAP_WIDTH - 1, 9));
+            assert_eq!(data.stats[0].hp, *hp);
+            assert_eq!(data.snakes[0].alive, *hp > 0);
         }
-        for i in 1..data.length as usize - 1 {
-            if data.snake_infos[0].x == data.snake_infos[i].x && data.snake_infos[0].y == data.snake_infos[i].y {
-                n = data.length + 1;    //判断蛇是否撞到自
+    }
+
+    #[test]
+    fn items_expire_and_respect_caps() {
+        let mut data = game(GameMode::Single, (2, 9, Direction::Right));
+        data.rules.wall = WallRule::FreeWrap;
+        let star = 1;
+        data.items[star].chance = 1000;
+        data.items[star].max = 3;
+        data.items[star].lifetime = 5;
+        for _ in 0..20 {
+            step(&mut data);
+            assert!(count_items(&data, star) <= 3);
+            for x in 0..26 {
+                for y in 0..22 {
+                    if data.map[x][y] == ItemType::Item(star) {
+                        assert!(data.expires[x][y] > data.tick && data.expires[x][y] <= data.tick + 5);
+                    }
+                }
             }
         }
-        if n >= data.length { //当蛇撞到自己
-            data.hp = 0; //将蛇死亡
-        }
-        if data.map[(data.snake_infos[0].x - 1) as usize / 2][data.snake_infos[0].y as usize - 1] == ItemType::Barrier { //当蛇障碍物时
-            data.hp -= 1;//将生命值减一
-            data.map[(data.snake_infos[0].x - 1) as usize / 2][data.snake_infos[0].y as usize - 1] = ItemType::None;
+        data.items[star].chance = 0;
+        for _ in 0..5 {
+            step(&mut data);
         }
-        if data.hp == 0 {
-            move_cursor(25, 5);
-            set_color(data.colors.white);//调成白色
-            addstr("游戏结束！！！");
-            thread::sleep(Duration::from_secs(3));//延时
-            return true;
+        assert_eq!(count_items(&data, star), 0); //存在时间到了就消失
+        assert_eq!(count_items(&data, 0), 1); //食物一直保持一个
+    }
+
+    #[test]
+    fn combos_chain_food_within_the_window() {
+        let mut data = game(GameMode::Single, (2, 9, Direction::Right));
+        let eat = |data: &mut GameData, tick: u32| {
+            data.tick = tick;
+            data.map[10][10] = ItemType::Item(0);
+            collect_item(data, 0, 10, 10);
+            data.stats[0].combo
+        };
+        assert_eq!(eat(&mut data, 0), 1);
+        assert_eq!(data.stats[0].score, 30);
+        assert_eq!(eat(&mut data, 10), 2); //第0步吃到的食物也能开始连击
+        assert_eq!(data.stats[0].score, 90);
+        assert_eq!(eat(&mut data, 10 + score::COMBO_TICKS), 3);
+        data.tick = 10 + 2 * score::COMBO_TICKS;
+        step(&mut data);
+        assert_eq!(data.stats[0].combo, 1); //超过时间窗口,连击结束
+        for i in 0..20 {
+            eat(&mut data, 100 + i);
         }
-        if data.length == 81 {
-            move_cursor(25, 5);
-            set_color(data.colors.white);//调成白色
-            addstr("恭喜你过关！！！");
-            thread::sleep(Duration::from_secs(3));//延时
-            return true;
+        assert_eq!(data.stats[0].combo, score::MAX_COMBO);
+    }
+
+    #[test]
+    fn versus_ends_with_the_last_player_standing() {
+        let mut data = game(GameMode::Versus, (2, 9, Direction::Right));
+        assert_eq!(data.snakes.len(), 2);
+        assert_ne!(data.snakes[0].stats, data.snakes[1].stats);
+        assert_eq!(game_outcome(&data), None);
+        data.snakes[0].alive = false;
+        assert_eq!(game_outcome(&data), Some(String::from("玩家2获胜！！！")));
+        data.snakes[1].alive = false;
+        assert_eq!(game_outcome(&data), Some(String::from("平局！！！")));
+        let mut data = game(GameMode::Versus, (2, 9, Direction::Right));
+        data.stage.goal_length = 10;
+        data.snakes[1].length = 10;
+        assert_eq!(game_outcome(&data), Some(String::from("玩家2获胜！！！"))); //先达到目标的玩家获胜
+    }
+
+    #[test]
+    fn head_on_crash_is_a_draw() {
+        let mut data = game(GameMode::Versus, (2, 9, Direction::Right));
+        data.stage.spawn2 = Some((4, 9, Direction::Left));
+        load_stage(&mut data);
+        step(&mut data);
+        assert!(!data.snakes[0].alive && !data.snakes[1].alive);
+        assert_eq!(data.stats[0].last_hit, Some(Cause::Crash));
+        assert_eq!(game_outcome(&data), Some(String::from("平局！！！")));
+    }
+
+    #[test]
+    fn coop_shares_one_life_pool() {
+        let mut data = game(GameMode::Coop, (2, 9, Direction::Right));
+        assert_eq!(data.stats.len(), 1);
+        assert_eq!(data.snakes[1].stats, 0);
+        data.map[3][9] = ItemType::Item(BARRIER); //两条蛇前方各有一个障碍物
+        data.map[22][12] = ItemType::Item(BARRIER);
+        step(&mut data);
+        assert_eq!(data.stats[0].hp, 4);
+        data.stage.goal_length = 8;
+        assert_eq!(game_outcome(&data), None);
+        data.snakes[1].length = 5; //两条蛇的长度之和达到目标
+        assert_eq!(game_outcome(&data), Some(String::from("恭喜你过关！！！")));
+        data.snakes[1].alive = false;
+        assert_eq!(game_outcome(&data), Some(String::from("游戏结束！！！")));
+    }
+
+    #[test]
+    fn bots_spawn_apart_and_are_not_players() {
+        for seed in 1..20 {
+            let mut data = test_game(builtin_items());
+            data.seed = seed;
+            data.bots = MAX_BOTS;
+            data.bot_agent = AgentKind::Bfs;
+            data.stage = Level::builtin(4);
+            load_stage(&mut data);
+            assert_eq!(data.snakes.len(), 1 + MAX_BOTS);
+            let mut cells = Vec::new();
+            for (i, snake) in data.snakes.iter().enumerate().skip(1) {
+                assert!(!snake.player);
+                assert_eq!(snake.stats, i); //电脑对手有自己的生命值
+                assert_eq!(snake.controller, Controller::Agent(AgentKind::Bfs));
+            }
+            for snake in data.snakes.iter() {
+                for body in snake.body[..snake.length as usize].iter() {
+                    let (x, y) = snake_cell(body);
+                    assert_ne!(data.map[x][y], ItemType::Wall);
+                    cells.push((x, y));
+                }
+            }
+            let count = cells.len();
+            cells.sort();
+            cells.dedup();
+            assert_eq!(cells.len(), count); //蛇之间没有重叠
         }
-        update_ui(data);//用来随机产生障碍物以及食物和生命药水以及用来判断游戏的各种参数（小星星是否吃到，是否撞墙)
+        let mut data = game(GameMode::Single, (2, 9, Direction::Right));
+        data.bots = 1;
+        load_stage(&mut data);
+        data.snakes[1].alive = false;
+        assert_eq!(game_outcome(&data), None); //电脑对手死亡不影响游戏
+        data.snakes[0].alive = false;
+        assert_eq!(game_outcome(&data), Some(String::from("游戏结束！！！")));
+    }
+
+    #[test]
+    fn records_every_hp_change() {
+        let mut stats = Stats::new(5);
+        stats.combo = 3;
+        stats.hurt(2, Cause::Border, 4);
+        assert_eq!((stats.hp, stats.combo, stats.last_hit), (3, 1, Some(Cause::Border))); //受伤时连击中断
+        stats.heal(5, Cause::Item(1), 9);
+        stats.heal(1, Cause::Item(1), 10); //已经满了,不记录
+        let history: Vec<(u32, i8, i8)> = stats.history.iter().map(|change| (change.tick, change.change, change.hp)).collect();
+        assert_eq!(history, vec![(4, -2, 3), (9, 3, 6)]);
+        stats.hurt(i8::max_value(), Cause::Bite, 11);
+        stats.hurt(i8::max_value(), Cause::Bite, 12);
+        assert_eq!(stats.hp, i8::min_value()); //不会溢出
+        let mut data = game(GameMode::Single, (2, 9, Direction::Right));
+        data.map[3][9] = ItemType::Item(BARRIER);
+        step(&mut data);
+        let change = data.stats[0].history[0];
+        assert_eq!((change.tick, change.change, change.hp, change.cause), (1, -1, 5, Cause::Item(BARRIER)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn zen_crashes_and_speed_changes_are_harmless() {
+        let mut data = game(GameMode::Coop, (2, 9, Direction::Right));
+        data.rules.variant = Variant::Zen;
+        data.stage.spawn2 = Some((4, 9, Direction::Left));
+        load_stage(&mut data);
+        step(&mut data); //两个蛇头相撞
+        assert!(data.snakes[0].alive && data.snakes[1].alive);
+        assert_eq!(data.stats[0].hp, 6);
+        assert!(data.stats[0].history.is_empty());
+        data.velocity = MAX_VELOCITY - 10; //第一关这样的慢速关卡减速时不会变快
+        change_speed(&mut data, rules::SPEED_STEP);
+        assert_eq!(data.velocity, MAX_VELOCITY);
+        data.velocity = MIN_VELOCITY;
+        change_speed(&mut data, -rules::SPEED_STEP);
+        assert_eq!(data.velocity, MIN_VELOCITY);
+    }
+}
+
@@ -0,0 +1,560 @@
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ncursesw::*;
+
+use crate::ai::{AgentKind, GameView};
+use crate::items::ItemKind;
+use crate::json::{self, Json};
+use crate::rules::GameMode;
+use crate::simulate::{self, Options};
+use crate::sync::{self, direction_name, object, parse_direction, Mirror};
+use crate::{add_bot, addstr, clear_screen, draw_board, draw_frame, draw_level, fit, free_spawn, game_outcome, move_cursor, new_snake};
+use crate::{set_color, set_cursor_visiable, step, timestamp, update_panel, wasd_direction};
+use crate::{ColorName, Controller, Direction, GameData, Stats, MAX_TURNS};
+
+pub const DEFAULT_PORT: u16 = 7878;
+const MAX_PLAYERS: usize = 8; //一局最多的玩家数
+const SEND_BUFFER: usize = 64; //每个客户端最多积压的消息数,超过时断开连接
+
+const PLAYER_COLORS: [(ColorName, ColorName); MAX_PLAYERS] = [ //每个玩家蛇头和蛇身的颜色,前两个与本地双人游戏相同
+    (ColorName::Red, ColorName::Yellow),
+    (ColorName::Green, ColorName::Cyan),
+    (ColorName::Yellow, ColorName::Red),
+    (ColorName::Cyan, ColorName::Green),
+    (ColorName::White, ColorName::Yellow),
+    (ColorName::Red, ColorName::Cyan),
+    (ColorName::Green, ColorName::Yellow),
+    (ColorName::Yellow, ColorName::Green)
+];
+
+const SERVE_USAGE: &str = "用法：snake-rust serve [--port 端口] [--players 2-8] [--level 1-6 | --map 地图文件]
+                 [--wall wrap|solid|free] [--bots 数量] [--bot-agent 策略] [--hp 生命值]";
+
+struct Client {
+    id: usize, //连接的编号,不会重复
+    stream: TcpStream, //只用来断开连接,发送交给写入线程
+    outbox: SyncSender<String>, //等待写入线程发送的消息
+    player: Option<usize>, //在这一局中控制的蛇,None表示等待下一局
+    connected: bool
+}
+
+impl Client {
+    fn send(&mut self, message: &Json) {//不等待发送完成,积压太多或发送失败时当作断开连接
+        if self.connected && self.outbox.try_send(message.to_string()).is_err() {
+            self.connected = false;
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+struct Server {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    next_id: usize,
+    sender: Sender<(usize, Option<Json>)>, //交给读取线程,None表示连接已断开
+    receiver: Receiver<(usize, Option<Json>)>,
+    players: usize //凑够这么多玩家后开始
+}
+
+pub fn run_serve(args: &[String], items: Vec<ItemKind>) -> Result<(), String> {//联网对战的服务器,负责推进游戏并把变化发给所有的客户端
+    let mut port = DEFAULT_PORT;
+    let mut players = 2;
+    let mut rest = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--port" => port = args.get(i + 1).and_then(|value| value.parse().ok())
+                .ok_or(format!("端口必须是数字\n{}", SERVE_USAGE))?,
+            "--players" => players = match args.get(i + 1).and_then(|value| value.parse().ok()) {
+                Some(n @ 2..=MAX_PLAYERS) => n,
+                _ => return Err(format!("玩家数必须是2-{}\n{}", MAX_PLAYERS, SERVE_USAGE))
+            },
+            _ => {
+                rest.push(args[i].clone());
+                rest.extend(args.get(i + 1).cloned());
+            }
+        }
+        i += 2;
+    }
+    let options = simulate::parse_options(&rest).map_err(|message| format!("{}\n{}", message, SERVE_USAGE))?;
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("无法监听端口{}：{}", port, e))?;
+    let mut server = Server::new(listener, players)?;
+    let mut data = simulate::headless_game(items, &options);
+    println!("服务器已在端口{}启动,凑够{}名玩家后开始", port, players);
+    loop {
+        server.wait_players();
+        server.play_round(&mut data, &options);
+    }
+}
+
+impl Server {
+    fn new(listener: TcpListener, players: usize) -> Result<Server, String> {
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let (sender, receiver) = mpsc::channel();
+        Ok(Server { listener: listener, clients: Vec::new(), next_id: 0, sender: sender, receiver: receiver, players: players })
+    }
+
+    fn accept(&mut self) {//接受新的连接,游戏进行中连接的玩家等到下一局再加入
+        while let Ok((stream, address)) = self.listener.accept() {
+            let _ = stream.set_nonblocking(false);
+            let _ = stream.set_nodelay(true);
+            let writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(_) => continue
+            };
+            let (outbox, queue) = mpsc::sync_channel(SEND_BUFFER);
+            thread::spawn(move || write_messages(writer, queue));
+            let mut client = Client { id: self.next_id, stream: stream, outbox: outbox, player: None, connected: true };
+            self.next_id += 1;
+            if self.online() >= MAX_PLAYERS {
+                client.send(&object(vec![("type", Json::String(String::from("error"))), ("message", Json::String(String::from("服务器已满")))]));
+                continue;
+            }
+            if let Ok(reader) = client.stream.try_clone() {
+                let (id, sender) = (client.id, self.sender.clone());
+                thread::spawn(move || read_messages(id, reader, sender));
+            }
+            println!("{}已连接", address);
+            self.clients.push(client);
+        }
+    }
+
+    fn online(&self) -> usize {
+        self.clients.iter().filter(|client| client.connected).count()
+    }
+
+    fn broadcast(&mut self, message: &Json) {
+        for client in self.clients.iter_mut() {
+            client.send(message);
+        }
+    }
+
+    fn poll(&mut self, mut data: Option<&mut GameData>) {//处理客户端发来的转向和断开连接
+        while let Ok((id, message)) = self.receiver.try_recv() {
+            let index = match self.clients.iter().position(|client| client.id == id) {
+                Some(index) => index,
+                None => continue
+            };
+            let player = self.clients[index].player;
+            if let Some(message) = message {
+                match message.get("type").and_then(Json::as_str) {
+                    Some("turn") => {
+                        let direction = message.get("direction").and_then(Json::as_str).and_then(parse_direction);
+                        if let (Some(direction), Some(player), Some(data)) = (direction, player, data.as_mut()) {
+                            let snake = &mut data.snakes[player];
+                            if snake.turns.len() < MAX_TURNS {
+                                snake.turns.push(direction);
+                            }
+                        }
+                        continue;
+                    },
+                    Some("quit") => {},
+                    _ => continue
+                }
+            }
+            let connected = self.clients[index].connected; //主动离开后还会收到一次连接断开
+            self.clients[index].connected = false;
+            let _ = self.clients[index].stream.shutdown(Shutdown::Both);
+            match (player, data.as_mut()) {
+                (Some(player), Some(data)) if connected || data.snakes[player].alive => { //断开的玩家的蛇直接出局,游戏继续
+                    data.snakes[player].alive = false;
+                    println!("玩家{}断开连接", player + 1);
+                    self.broadcast(&object(vec![("type", Json::String(String::from("left"))), ("player", Json::Number(player as f64))]));
+                },
+                (None, _) if connected => println!("一名等待中的玩家断开连接"),
+                _ => {}
+            }
+        }
+    }
+
+    fn wait_players(&mut self) {//等待玩家连接,人数变化时通知所有的客户端
+        let mut last = usize::max_value();
+        loop {
+            self.accept();
+            self.poll(None);
+            self.clients.retain(|client| client.connected);
+            let online = self.clients.len();
+            if online != last {
+                println!("等待玩家：{}/{}", online, self.players);
+                let message = object(vec![
+                    ("type", Json::String(String::from("lobby"))),
+                    ("players", Json::Number(online as f64)),
+                    ("needed", Json::Number(self.players as f64))
+                ]);
+                self.broadcast(&message);
+                last = online;
+            }
+            if online >= self.players {
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn play_round(&mut self, data: &mut GameData, options: &Options) {//所有已连接的玩家一起玩一局
+        start_round(data, options, self.clients.len());
+        for (i, client) in self.clients.iter_mut().enumerate() {
+            client.player = Some(i);
+        }
+        println!("开始：{}名玩家,{}", self.clients.len(), data.stage.name);
+        let snapshot = sync::snapshot(data);
+        for client in self.clients.iter_mut() {
+            let mut message = snapshot.clone();
+            if let Json::Object(fields) = &mut message {
+                fields.push((String::from("player"), Json::Number(client.player.unwrap() as f64)));
+            }
+            client.send(&message);
+        }
+        let mut mirror = Mirror::new(data);
+        let start = Instant::now();
+        loop {
+            thread::sleep(Duration::from_millis(data.velocity as u64));//控制蛇的移动速度
+            let joined = self.clients.len();
+            self.accept();
+            for client in self.clients[joined..].iter_mut() { //新连接的玩家等待下一局
+                client.send(&object(vec![("type", Json::String(String::from("wait")))]));
+            }
+            self.poll(Some(data));
+            if self.clients.iter().all(|client| client.player.is_none() || !client.connected) {
+                println!("所有玩家都已离开");
+                break;
+            }
+            step(data);
+            data.t1 = start.elapsed().as_millis() as i64;
+            let diff = mirror.diff(data);
+            self.broadcast_playing(&diff);
+            if let Some(message) = game_outcome(data) {
+                println!("{}", message);
+                self.broadcast_playing(&object(vec![("type", Json::String(String::from("over"))), ("message", Json::String(message))]));
+                thread::sleep(Duration::from_secs(3));
+                break;
+            }
+        }
+        self.clients.retain(|client| client.connected);
+        for client in self.clients.iter_mut() {
+            client.player = None;
+        }
+    }
+
+    fn broadcast_playing(&mut self, message: &Json) {//只发给这一局中的玩家
+        for client in self.clients.iter_mut().filter(|client| client.player.is_some()) {
+            client.send(message);
+        }
+    }
+}
+
+fn start_round(data: &mut GameData, options: &Options, players: usize) {//第1、2名玩家使用关卡的出生点,其他玩家和电脑对手随机出生
+    data.mode = GameMode::Versus;
+    data.bots = 0;
+    simulate::start(data, options, timestamp() as u64);
+    data.t1 = 0;
+    for i in 2..players {
+        let spawn = free_spawn(data);
+        let stats = data.stats.len();
+        data.stats.push(Stats::new(data.start_hp));
+        let (head, body) = PLAYER_COLORS[i];
+        data.snakes.push(new_snake(spawn, stats, Controller::Remote, head, body));
+    }
+    for i in 0..data.snakes.len() {
+        data.snakes[i].controller = Controller::Remote;
+        data.snakes[i].head_color = PLAYER_COLORS[i].0;
+        data.snakes[i].body_color = PLAYER_COLORS[i].1;
+    }
+    data.bots = options.bots;
+    for _ in 0..options.bots {
+        add_bot(data);
+    }
+}
+
+pub fn read_messages<R: Read>(id: usize, stream: R, sender: Sender<(usize, Option<Json>)>) {//每个连接一个线程,把收到的每一行交给主线程
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        if let Ok(message) = json::parse(&line) {
+            if sender.send((id, Some(message))).is_err() {
+                return;
+            }
+        }
+    }
+    let _ = sender.send((id, None));
+}
+
+fn write_messages(mut stream: TcpStream, queue: Receiver<String>) {//每个连接一个写入线程,慢的客户端不会拖住整局游戏
+    for line in queue.iter() {
+        if writeln!(stream, "{}", line).is_err() {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+pub fn run_join(data: &mut GameData, args: &[String]) -> Result<(), String> {//连接到服务器,在本地显示服务器发来的游戏状态
+    let address = if args[0].contains(':') { args[0].clone() } else { format!("{}:{}", args[0], DEFAULT_PORT) };
+    let agent = match args.get(1).map(String::as_str) { //由Agent代替键盘操作,方便测试
+        Some("--agent") => Some(args.get(2).and_then(|name| AgentKind::parse(name)).ok_or("用法：snake-rust join 地址:端口 [--agent 策略]")?),
+        Some(_) => return Err(String::from("用法：snake-rust join 地址:端口 [--agent 策略]")),
+        None => None
+    };
+    let mut stream = TcpStream::connect(&address).map_err(|e| format!("无法连接{}：{}", address, e))?;
+    let _ = stream.set_nodelay(true);
+    let reader = stream.try_clone().map_err(|e| e.to_string())?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || read_messages(0, reader, sender));
+    let mut me: Option<usize> = None;
+    let mut online: Vec<bool> = Vec::new();
+    set_cursor_visiable(false);
+    data.mode = GameMode::Versus;
+    draw_frame(data);
+    clear_screen();
+    notice(data, &format!("正在连接{}……", address));
+    cbreak().unwrap();
+    loop {
+        loop {
+            let message = match receiver.try_recv() {
+                Ok((_, Some(message))) => message,
+                Ok((_, None)) | Err(TryRecvError::Disconnected) => {
+                    notice(data, "与服务器的连接已断开");
+                    thread::sleep(Duration::from_secs(2));
+                    return Ok(());
+                },
+                Err(TryRecvError::Empty) => break
+            };
+            match message.get("type").and_then(Json::as_str) {
+                Some("lobby") => {
+                    me = None;
+                    clear_screen();
+                    notice(data, &format!("等待玩家加入：{}/{}", number(&message, "players"), number(&message, "needed")));
+                },
+                Some("wait") => {
+                    if me.is_none() {
+                        notice(data, "游戏正在进行,下一局开始时加入");
+                    }
+                },
+                Some("snapshot") => {
+                    sync::apply(data, &message)?;
+                    let player = player_index(&message, data)?;
+                    me = Some(player);
+                    online = vec![true; data.snakes.len()];
+                    draw_frame(data);
+                    draw_sidebar(data, player);
+                    update_net(data, player, &online);
+                },
+                Some("tick") => {
+                    if let Some(me) = me {
+                        sync::apply(data, &message)?;
+                        update_net(data, me, &online);
+                        if let (Some(kind), true) = (agent, data.snakes[me].alive) {
+                            if let Some(direction) = kind.agent().decide(&GameView::new(data, me)) {
+                                send_turn(&mut stream, direction);
+                            }
+                        }
+                    }
+                },
+                Some("left") => {
+                    if let Some(flag) = online.get_mut(number(&message, "player")) {
+                        *flag = false;
+                    }
+                },
+                Some("over") => {
+                    move_cursor(25, 5);
+                    set_color(data.colors.white);//调成白色
+                    addstr(message.get("message").and_then(Json::as_str).unwrap_or(""));
+                    refresh().unwrap();
+                },
+                Some("error") => {
+                    notice(data, message.get("message").and_then(Json::as_str).unwrap_or(""));
+                    thread::sleep(Duration::from_secs(2));
+                    return Ok(());
+                },
+                _ => {}
+            }
+        }
+        timeout(Duration::from_millis(20)).unwrap(); //等待按键的同时检查服务器发来的消息
+        let key = getch();
+        nodelay(stdscr(), false).unwrap();
+        let direction = match key {
+            Ok(CharacterResult::Character('\u{001B}')) | Ok(CharacterResult::Key(KeyBinding::Exit)) => { //Esc键离开
+                let _ = writeln!(stream, "{}", object(vec![("type", Json::String(String::from("quit")))]));
+                return Ok(());
+            },
+            Ok(CharacterResult::Key(key)) => Direction::try_from(&key).ok(),
+            Ok(CharacterResult::Character(ch)) => wasd_direction(ch),
+            Err(_) => None
+        };
+        if let (Some(direction), Some(_)) = (direction, me) {
+            send_turn(&mut stream, direction);
+        }
+    }
+}
+
+fn send_turn(stream: &mut TcpStream, direction: Direction) {
+    let _ = writeln!(stream, "{}", object(vec![
+        ("type", Json::String(String::from("turn"))),
+        ("direction", Json::String(String::from(direction_name(direction))))
+    ]));
+}
+
+fn player_index(message: &Json, data: &GameData) -> Result<usize, String> {//服务器指定的玩家必须是场上的一条蛇
+    match message.get("player").and_then(Json::as_f64) {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 && (n as usize) < data.snakes.len() => Ok(n as usize),
+        _ => Err(String::from("服务器发来的玩家编号无效"))
+    }
+}
+
+fn number(message: &Json, key: &str) -> usize {
+    message.get(key).and_then(Json::as_f64).unwrap_or(0.0) as usize
+}
+
+pub fn notice(data: &GameData, text: &str) {//在地图中间输出提示
+    move_cursor(15, 5);
+    set_color(data.colors.yellow);//调成黄色
+    addstr(&fit(text, 38));
+    refresh().unwrap();
+}
+
+fn draw_sidebar(data: &GameData, me: usize) {//在双人对战的信息栏上改为显示所有玩家的状态
+    set_color(data.colors.yellow);//调成黄色
+    move_cursor(56, 1);
+    addstr(&fit(&format!("联网对战：你是玩家{}", me + 1), 22));
+    move_cursor(56, 12);
+    addstr(&fit("所有玩家：", 22));
+    move_cursor(56, 17);
+    addstr(&fit("转向：方向键或WASD键", 22));
+    move_cursor(56, 18);
+    addstr(&fit("离开：Esc键", 22));
+}
+
+fn update_net(data: &GameData, me: usize, online: &[bool]) {//输出时间、关卡、自己的状态栏、所有玩家和地图
+    move_cursor(66, 2);
+    set_color(data.colors.red);//调成红色
+    addstr(&format!("{:<8}", data.t1 / 1000)); //游戏已用时间
+    draw_level(data);
+    update_panel(data, me, 3);
+    for row in 0..4 { //每行两名玩家
+        for column in 0..2 {
+            let i = row * 2 + column;
+            move_cursor(56 + column as i32 * 11, 13 + row as i32);
+            let snake = match data.snakes.get(i).filter(|snake| snake.player) {
+                Some(snake) => snake,
+                None => {
+                    addstr(&fit("", 11));
+                    continue;
+                }
+            };
+            let state = if !online.get(i).cloned().unwrap_or(true) {
+                String::from("离线")
+            } else if !snake.alive {
+                String::from("出局")
+            } else {
+                format!("{}", snake.length - 1)
+            };
+            set_color(data.colors.get(snake.head_color));
+            addstr(&fit(&format!("{}P{} {}", if i == me { '>' } else { ' ' }, i + 1, state), 11));
+        }
+    }
+    draw_board(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    use crate::items::builtin_items;
+    use crate::test_game;
+
+    fn test_options() -> Options {
+        let mut options = simulate::parse_options(&[String::from("--level"), String::from("6")]).unwrap();
+        options.stage.velocity = 20;
+        options
+    }
+
+    fn scripted_client(address: SocketAddr, turn: Direction) -> (usize, u32, usize, Direction) {//收到开局状态后转向,走几步后离开
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+        let mut data = simulate::configure(test_game(builtin_items()), &test_options());
+        let mut me = None;
+        loop {
+            let message = json::parse(&lines.next().unwrap().unwrap()).unwrap();
+            match message.get("type").and_then(Json::as_str) {
+                Some("lobby") => continue,
+                Some("snapshot") => {
+                    sync::apply(&mut data, &message).unwrap();
+                    me = Some(player_index(&message, &data).unwrap());
+                    send_turn(&mut stream, turn);
+                },
+                Some("tick") => {
+                    sync::apply(&mut data, &message).unwrap();
+                    if data.tick >= 5 {
+                        let _ = writeln!(stream, "{}", object(vec![("type", Json::String(String::from("quit")))]));
+                        let me = me.unwrap();
+                        return (me, data.tick, data.snakes.len(), data.snakes[me].body[0].direction);
+                    }
+                },
+                other => panic!("意外的消息：{:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn plays_a_round_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut server = Server::new(listener, 2).unwrap();
+        let clients: Vec<_> = [Direction::Up, Direction::Down].iter().map(|turn| {
+            let turn = *turn;
+            thread::spawn(move || scripted_client(address, turn))
+        }).collect();
+        let options = test_options();
+        let mut data = simulate::configure(test_game(builtin_items()), &options);
+        server.wait_players();
+        server.play_round(&mut data, &options);
+        let mut results: Vec<_> = clients.into_iter().map(|client| client.join().unwrap()).collect();
+        results.sort_by_key(|result| result.0);
+        assert_eq!(results.iter().map(|result| result.0).collect::<Vec<_>>(), vec![0, 1]);
+        for (player, tick, snakes, direction) in results {
+            assert!(tick >= 5);
+            assert_eq!(snakes, 2);
+            assert_eq!(direction, if player == 0 { Direction::Up } else { Direction::Down }); //服务器执行了客户端的转向
+        }
+    }
+
+    #[test]
+    fn drops_clients_that_stop_reading() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut server = Server::new(listener, 2).unwrap();
+        let _stalled = TcpStream::connect(address).unwrap(); //连接后从不读取
+        while server.clients.is_empty() {
+            server.accept();
+            thread::sleep(Duration::from_millis(10));
+        }
+        let message = Json::String("x".repeat(64 * 1024));
+        let start = Instant::now();
+        for _ in 0..1000 {
+            server.broadcast(&message);
+        }
+        assert!(start.elapsed() < Duration::from_secs(1)); //发送不会等待客户端
+        assert!(!server.clients[0].connected);
+    }
+
+    #[test]
+    fn rejects_invalid_player_index() {
+        let mut data = simulate::configure(test_game(builtin_items()), &test_options());
+        simulate::start(&mut data, &test_options(), 1);
+        let message = |player: f64| object(vec![("player", Json::Number(player))]);
+        assert_eq!(player_index(&message(0.0), &data), Ok(0));
+        assert!(player_index(&message(data.snakes.len() as f64), &data).is_err());
+        assert!(player_index(&message(-1.0), &data).is_err());
+        assert!(player_index(&message(0.5), &data).is_err());
+        assert!(player_index(&Json::Null, &data).is_err());
+    }
+}
@@ -0,0 +1,513 @@
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+use ncursesw::*;
+use rand::XorShiftRng;
+
+use crate::ai::AgentKind;
+use crate::level::{spawn_char, spawn_direction, Level, MAP_HEIGHT, MAP_WIDTH};
+use crate::rules::{GameMode, Variant, WallRule};
+use crate::scheduler::Scheduler;
+use crate::{addstr, draw_board, draw_frame, fit, game_outcome, load_stage, move_cursor, set_color, set_cursor_visiable, step, update_data};
+use crate::{Direction, GameData, ItemType, Snake, Stats};
+
+const CHECKPOINT_TICKS: u32 = 100; //每隔这么多步保存一次状态,后退时从最近的状态重新计算
+const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const SEEK_TICKS: u32 = 50; //[和]键跳过的步数
+pub const AUTOPLAY_SPEED: f64 = 8.0; //转换成asciicast时的播放速度,录制的时间会还原成1倍速
+const AUTOPLAY_HOLD: u64 = 250; //转换时最后一帧停留的时间(毫秒)
+
+#[derive(Clone)]
+pub struct Replay {//同样的种子、设置和按键可以重现同样的一局
+    pub seed: u64,
+    pub mode: GameMode,
+    pub level: i8, //关卡编号,自定义地图为0
+    pub stage: Level,
+    pub wall: WallRule, //全局的撞墙规则,关卡中的设置保存在stage里
+    pub variant: Variant, //玩法
+    pub hp: i8, //开局的生命值
+    pub bots: usize,
+    pub bot_agent: AgentKind,
+    pub autopilot: Option<AgentKind>,
+    pub turns: Vec<(u32, usize, Direction)>, //在第几步把哪个方向交给第几条蛇
+    pub speeds: Vec<(u32, i32)>, //休闲玩法在第几步把移动间隔调成多少毫秒
+    pub ticks: u32 //这一局一共的步数
+}
+
+impl Replay {
+    pub fn new(data: &GameData) -> Replay {//记录这一局开始时的设置
+        Replay {
+            seed: data.seed,
+            mode: data.mode,
+            level: data.level,
+            stage: data.stage.clone(),
+            wall: data.rules.wall,
+            variant: data.rules.variant,
+            hp: data.start_hp,
+            bots: data.bots,
+            bot_agent: data.bot_agent,
+            autopilot: data.autopilot,
+            turns: Vec::new(),
+            speeds: Vec::new(),
+            ticks: 0
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("无法读取{}：{}", path, e))?;
+        Replay::parse(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("无法保存{}：{}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut replay = Replay {
+            seed: 1,
+            mode: GameMode::Single,
+            level: 0,
+            stage: Level::blank(),
+            wall: WallRule::WrapPenalty,
+            variant: Variant::Classic,
+            hp: 6,
+            bots: 0,
+            bot_agent: AgentKind::Greedy,
+            autopilot: None,
+            turns: Vec::new(),
+            speeds: Vec::new(),
+            ticks: 0
+        };
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "stage:" { //后面是关卡地图,格式与地图文件相同
+                replay.stage = Level::parse(&lines.collect::<Vec<&str>>().join("\n"))?;
+                replay.stage.number = replay.level;
+                return Ok(replay);
+            }
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => return Err(format!("无法识别：{}", line))
+            };
+            match key {
+                "seed" => replay.seed = value.parse().map_err(|_| "种子必须是数字")?,
+                "mode" => replay.mode = GameMode::parse(value).ok_or(format!("未知的游戏模式：{}", value))?,
+                "level" => replay.level = value.parse().map_err(|_| "关数必须是数字")?,
+                "wall" => replay.wall = WallRule::parse(value).ok_or(format!("未知的撞墙规则：{}", value))?,
+                "variant" => replay.variant = Variant::parse(value).ok_or(format!("未知的玩法：{}", value))?,
+                "hp" => replay.hp = value.parse().map_err(|_| "生命值必须是数字")?,
+                "bots" => replay.bots = value.parse().map_err(|_| "电脑对手数量必须是数字")?,
+                "bot_agent" => replay.bot_agent = AgentKind::parse(value).ok_or(format!("未知的策略：{}", value))?,
+                "autopilot" => replay.autopilot = Some(AgentKind::parse(value).ok_or(format!("未知的策略：{}", value))?),
+                "ticks" => replay.ticks = value.parse().map_err(|_| "步数必须是数字")?,
+                "turn" => replay.turns.push(parse_turn(value).ok_or(format!("turn的格式应为步数,蛇,方向：{}", value))?),
+                "speed" => replay.speeds.push(parse_speed(value).ok_or(format!("speed的格式应为步数,毫秒：{}", value))?),
+                _ => return Err(format!("未知的设置：{}", key))
+            }
+        }
+        Err(String::from("回放中没有关卡地图"))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# snake-rust replay\n");
+        text += &format!("seed={}\n", self.seed);
+        text += &format!("mode={}\n", self.mode.key());
+        text += &format!("level={}\n", self.level);
+        text += &format!("wall={}\n", self.wall.key());
+        text += &format!("variant={}\n", self.variant.key());
+        text += &format!("hp={}\n", self.hp);
+        text += &format!("bots={}\n", self.bots);
+        text += &format!("bot_agent={}\n", self.bot_agent.key());
+        if let Some(kind) = self.autopilot {
+            text += &format!("autopilot={}\n", kind.key());
+        }
+        text += &format!("ticks={}\n", self.ticks);
+        for (tick, snake, direction) in self.turns.iter() {
+            text += &format!("turn={},{},{}\n", tick, snake, spawn_char(*direction));
+        }
+        for (tick, velocity) in self.speeds.iter() {
+            text += &format!("speed={},{}\n", tick, velocity);
+        }
+        text += "stage:\n";
+        text += &self.stage.to_text();
+        text
+    }
+
+    pub fn start(&self, data: &mut GameData) {//按回放的设置重新开始这一局
+        data.seed = self.seed;
+        data.mode = self.mode;
+        data.level = self.level;
+        data.stage = self.stage.clone();
+        data.rules.wall = self.wall;
+        data.rules.variant = self.variant;
+        data.start_hp = self.hp;
+        data.bots = self.bots;
+        data.bot_agent = self.bot_agent;
+        data.autopilot = self.autopilot;
+        load_stage(data);
+    }
+
+    pub fn advance(&self, data: &mut GameData) {//交给蛇这一步记录的转向和速度,然后前进一步
+        for (tick, velocity) in self.speeds.iter() {
+            if *tick == data.tick {
+                data.velocity = *velocity;
+            }
+        }
+        for (tick, snake, direction) in self.turns.iter() {
+            if *tick == data.tick && *snake < data.snakes.len() {
+                data.snakes[*snake].turns.push(*direction);
+            }
+        }
+        step(data);
+    }
+}
+
+fn parse_speed(text: &str) -> Option<(u32, i32)> {//解析"步数,毫秒"
+    let mut parts = text.split(',').map(|part| part.trim());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(tick), Some(velocity), None) => Some((tick.parse().ok()?, velocity.parse().ok()?)),
+        _ => None
+    }
+}
+
+fn parse_turn(text: &str) -> Option<(u32, usize, Direction)> {//解析"步数,蛇,方向"
+    let parts: Vec<&str> = text.split(',').map(|part| part.trim()).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    Some((parts[0].parse().ok()?, parts[1].parse().ok()?, spawn_direction(parts[2].chars().next()?)?))
+}
+
+struct Checkpoint {//某一步的游戏状态
+    tick: u32,
+    velocity: i32,
+    map: [[ItemType; MAP_HEIGHT]; MAP_WIDTH],
+    expires: [[u32; MAP_HEIGHT]; MAP_WIDTH],
+    scheduler: Scheduler,
+    snakes: Vec<Snake>,
+    stats: Vec<Stats>,
+    rng: XorShiftRng
+}
+
+impl Checkpoint {
+    fn save(data: &GameData) -> Checkpoint {
+        Checkpoint {
+            tick: data.tick,
+            velocity: data.velocity,
+            map: data.map,
+            expires: data.expires,
+            scheduler: data.scheduler.clone(),
+            snakes: data.snakes.clone(),
+            stats: data.stats.clone(),
+            rng: data.rng.clone()
+        }
+    }
+
+    fn restore(&self, data: &mut GameData) {
+        data.tick = self.tick;
+        data.velocity = self.velocity;
+        data.map = self.map;
+        data.expires = self.expires;
+        data.scheduler = self.scheduler.clone();
+        data.snakes = self.snakes.clone();
+        data.stats = self.stats.clone();
+        data.rng = self.rng.clone();
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Event {
+    Eat, //吃到食物
+    Damage //生命值减少
+}
+
+struct Timeline {//先把整局算一遍,记录每一步的时间、保存的状态和发生的事件
+    checkpoints: Vec<Checkpoint>,
+    elapsed: Vec<i64>, //到每一步为止用的时间(毫秒)
+    events: Vec<(u32, Event)>, //事件发生后的步数
+    end: u32
+}
+
+impl Timeline {
+    fn build(data: &mut GameData, replay: &Replay) -> Timeline {
+        let mut timeline = Timeline { checkpoints: Vec::new(), elapsed: vec![0], events: Vec::new(), end: 0 };
+        replay.start(data);
+        loop {
+            data.t1 = timeline.elapsed[data.tick as usize]; //限时玩法按回放中的时间判断是否结束
+            if data.tick % CHECKPOINT_TICKS == 0 {
+                timeline.checkpoints.push(Checkpoint::save(data));
+            }
+            if data.tick >= replay.ticks || game_outcome(data).is_some() {
+                break;
+            }
+            let before = data.stats.clone();
+            let velocity = data.velocity as i64;
+            replay.advance(data);
+            timeline.elapsed.push(timeline.elapsed[timeline.elapsed.len() - 1] + velocity);
+            if data.stats.iter().zip(before.iter()).any(|(now, old)| now.food > old.food) {
+                timeline.events.push((data.tick, Event::Eat));
+            }
+            if data.stats.iter().zip(before.iter()).any(|(now, old)| now.hp < old.hp) {
+                timeline.events.push((data.tick, Event::Damage));
+            }
+        }
+        timeline.end = data.tick;
+        timeline
+    }
+
+    fn seek(&self, data: &mut GameData, replay: &Replay, tick: u32) {//从最近的保存状态开始重新计算到指定的步数
+        let tick = tick.min(self.end);
+        let index = ((tick / CHECKPOINT_TICKS) as usize).min(self.checkpoints.len() - 1);
+        self.checkpoints[index].restore(data);
+        while data.tick < tick {
+            data.t1 = self.elapsed[data.tick as usize];
+            replay.advance(data);
+        }
+        data.t1 = self.elapsed[data.tick as usize];
+    }
+}
+
+struct Settings {//观看回放前的游戏设置,退出时恢复
+    seed: u64,
+    mode: GameMode,
+    level: i8,
+    stage: Level,
+    wall: WallRule,
+    variant: Variant,
+    start_hp: i8,
+    bots: usize,
+    bot_agent: AgentKind,
+    autopilot: Option<AgentKind>
+}
+
+pub fn run_viewer(data: &mut GameData, replay: &Replay, autoplay: bool) {//观看回放,可以暂停、调速、单步、跳到事件和后退,autoplay时只播放一遍游戏画面
+    let settings = Settings {
+        seed: data.seed,
+        mode: data.mode,
+        level: data.level,
+        stage: data.stage.clone(),
+        wall: data.rules.wall,
+        variant: data.rules.variant,
+        start_hp: data.start_hp,
+        bots: data.bots,
+        bot_agent: data.bot_agent,
+        autopilot: data.autopilot
+    };
+    let timeline = Timeline::build(data, replay);
+    timeline.seek(data, replay, 0);
+    set_cursor_visiable(false);
+    draw_frame(data);
+    if !autoplay {
+        draw_help(data);
+    }
+    cbreak().unwrap();
+    let mut playing = true;
+    let mut speed = 2; //SPEEDS中的位置,默认是1倍速
+    loop {
+        data.t1 = timeline.elapsed[data.tick as usize];
+        update_data(data);
+        draw_board(data);
+        if autoplay {
+            refresh().unwrap();
+            if data.tick >= timeline.end {
+                thread::sleep(Duration::from_millis(AUTOPLAY_HOLD));
+                break;
+            }
+        } else {
+            draw_timeline(data, &timeline, playing, speed);
+        }
+        let rate = if autoplay { AUTOPLAY_SPEED } else { SPEEDS[speed] };
+        let wait = if playing { (data.velocity as f64 / rate) as u64 } else { 1000 };
+        timeout(Duration::from_millis(wait.max(1))).unwrap(); //等待按键的同时控制播放速度
+        let key = getch();
+        nodelay(stdscr(), false).unwrap();
+        let tick = data.tick;
+        match key {
+            Err(_) => {
+                if playing && tick < timeline.end {
+                    replay.advance(data);
+                } else {
+                    playing = false;
+                }
+            },
+            Ok(CharacterResult::Character(' ')) => {
+                playing = !playing;
+                if playing && tick >= timeline.end { //播放完后从头开始
+                    timeline.seek(data, replay, 0);
+                }
+            },
+            Ok(CharacterResult::Key(KeyBinding::RightArrow)) => { //单步前进
+                playing = false;
+                if tick < timeline.end {
+                    replay.advance(data);
+                }
+            },
+            Ok(CharacterResult::Key(KeyBinding::LeftArrow)) => { //单步后退
+                playing = false;
+                timeline.seek(data, replay, tick.saturating_sub(1));
+            },
+            Ok(CharacterResult::Key(KeyBinding::UpArrow)) => speed = (speed + 1).min(SPEEDS.len() - 1),
+            Ok(CharacterResult::Key(KeyBinding::DownArrow)) => speed = speed.saturating_sub(1),
+            Ok(CharacterResult::Character('n')) | Ok(CharacterResult::Character('N')) => { //跳到下一个事件
+                if let Some((next, _)) = timeline.events.iter().find(|(at, _)| *at > tick) {
+                    timeline.seek(data, replay, *next);
+                }
+            },
+            Ok(CharacterResult::Character('b')) | Ok(CharacterResult::Character('B')) => { //跳到上一个事件
+                if let Some((previous, _)) = timeline.events.iter().rev().find(|(at, _)| *at < tick) {
+                    timeline.seek(data, replay, *previous);
+                }
+            },
+            Ok(CharacterResult::Character('[')) => timeline.seek(data, replay, tick.saturating_sub(SEEK_TICKS)),
+            Ok(CharacterResult::Character(']')) => timeline.seek(data, replay, tick + SEEK_TICKS),
+            Ok(CharacterResult::Key(KeyBinding::Home)) => timeline.seek(data, replay, 0),
+            Ok(CharacterResult::Key(KeyBinding::End)) => timeline.seek(data, replay, timeline.end),
+            Ok(CharacterResult::Character('\u{001B}')) | Ok(CharacterResult::Character('q')) | Ok(CharacterResult::Key(KeyBinding::Exit)) => break,
+            Ok(_) => {}
+        }
+    }
+    data.seed = settings.seed;
+    data.mode = settings.mode;
+    data.level = settings.level;
+    data.stage = settings.stage;
+    data.rules.wall = settings.wall;
+    data.rules.variant = settings.variant;
+    data.start_hp = settings.start_hp;
+    data.bots = settings.bots;
+    data.bot_agent = settings.bot_agent;
+    data.autopilot = settings.autopilot;
+    if autoplay {
+        return;
+    }
+    move_cursor(0, 24);
+    addstr(&fit("", 79));
+    draw_frame(data);
+    set_cursor_visiable(true);//显示光标
+}
+
+fn draw_help(data: &GameData) {//在撞墙规则提示的位置输出回放的按键
+    let help = ["空格：播放  ←→：单步", "↑↓：速度  N/B：事件", "[ ]：快进退 Esc：退出"];
+    set_color(data.colors.yellow);//调成黄色
+    for i in 0..help.len() {
+        move_cursor(56, 19 + i as i32);
+        addstr(&fit(help[i], 22));
+    }
+}
+
+fn draw_timeline(data: &GameData, timeline: &Timeline, playing: bool, speed: usize) {//在地图下方输出进度条,x表示受到伤害的位置
+    const WIDTH: usize = 30;
+    let end = timeline.end.max(1) as usize;
+    let mut bar: Vec<char> = (0..WIDTH).map(|i| if i < data.tick as usize * WIDTH / end { '=' } else { '-' }).collect();
+    for (at, event) in timeline.events.iter() {
+        if *event == Event::Damage {
+            bar[(*at as usize * WIDTH / end).min(WIDTH - 1)] = 'x';
+        }
+    }
+    move_cursor(0, 24);
+    set_color(data.colors.white);//调成白色
+    addstr(&fit(&format!("{} {:>5}x [{}] {:>5}/{:<5}步 {:>7.1}/{:.1}秒",
+        if playing { ">>" } else { "||" }, SPEEDS[speed], bar.into_iter().collect::<String>(), data.tick, timeline.end,
+        timeline.elapsed[data.tick as usize] as f64 / 1000.0, timeline.elapsed[timeline.end as usize] as f64 / 1000.0), 79));
+    refresh().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::builtin_items;
+    use crate::sync;
+    use crate::test_game;
+
+    fn recorded_game(variant: Variant) -> (GameData, Replay) {//第6关,玩家1交给Agent,随机种子固定
+        let mut data = test_game(builtin_items());
+        data.stage = Level::builtin(6);
+        data.level = 6;
+        data.seed = 42;
+        data.rules.variant = variant;
+        data.autopilot = Some(AgentKind::Greedy);
+        let mut replay = Replay::new(&data);
+        replay.ticks = 2000;
+        (data, replay)
+    }
+
+    #[test]
+    fn timeline_uses_replay_time() {
+        let (mut data, replay) = recorded_game(Variant::TimeAttack(1));
+        data.t1 = 120_000; //上一局留下的时间不能影响回放
+        let timeline = Timeline::build(&mut data, &replay);
+        let velocity = Level::builtin(6).velocity as u32;
+        assert_eq!(timeline.end, (1000 + velocity - 1) / velocity);
+        timeline.seek(&mut data, &replay, 3);
+        assert_eq!(data.t1, timeline.elapsed[3]);
+    }
+
+    fn play(replay: &Replay, ticks: u32) -> String {//按回放走若干步,返回完整的游戏状态
+        let mut data = test_game(builtin_items());
+        replay.start(&mut data);
+        while data.tick < ticks && game_outcome(&data).is_none() {
+            replay.advance(&mut data);
+        }
+        sync::snapshot(&data).to_string()
+    }
+
+    #[test]
+    fn round_trips_text() {
+        let (_, mut replay) = recorded_game(Variant::Zen);
+        replay.mode = GameMode::Coop;
+        replay.wall = WallRule::Solid;
+        replay.bots = 2;
+        replay.bot_agent = AgentKind::AStar;
+        replay.turns = vec![(0, 0, Direction::Up), (7, 1, Direction::Left)];
+        replay.speeds = vec![(3, 80), (9, 300)];
+        let text = replay.to_text();
+        let parsed = Replay::parse(&text).unwrap();
+        assert_eq!(parsed.to_text(), text);
+        assert_eq!(parsed.turns, replay.turns);
+        assert_eq!(parsed.speeds, replay.speeds);
+        assert_eq!(parsed.variant, Variant::Zen);
+        assert!(Replay::parse("seed=1\nturn=1,0,x\nstage:\n").is_err());
+        assert!(Replay::parse("seed=1\nvariant=time-0\nstage:\n").is_err());
+        assert!(Replay::parse("seed=1\n").is_err());
+    }
+
+    #[test]
+    fn replays_are_deterministic() {
+        let (_, mut replay) = recorded_game(Variant::Classic);
+        replay.autopilot = None;
+        replay.bots = 2;
+        replay.turns = vec![(2, 0, Direction::Up), (5, 0, Direction::Left), (9, 0, Direction::Down), (14, 0, Direction::Right)];
+        let first = play(&replay, 300);
+        assert_eq!(play(&replay, 300), first);
+        assert_eq!(play(&Replay::parse(&replay.to_text()).unwrap(), 300), first); //保存再读取后也相同
+        replay.seed = 43;
+        assert_ne!(play(&replay, 300), first);
+    }
+
+    #[test]
+    fn survival_score_is_steps_survived() {
+        let (mut data, replay) = recorded_game(Variant::Survival);
+        replay.start(&mut data);
+        for _ in 0..50 {
+            replay.advance(&mut data);
+        }
+        assert!(data.snakes[0].alive);
+        assert_eq!(data.stats[0].score, 50);
+    }
+
+    #[test]
+    fn survival_never_slows_down() {
+        let (mut data, mut replay) = recorded_game(Variant::Survival);
+        replay.stage.velocity = 30; //比生存玩法的最快速度还快的地图
+        replay.start(&mut data);
+        while data.tick < crate::rules::DIFFICULTY_TICKS {
+            replay.advance(&mut data);
+        }
+        assert!(data.snakes[0].alive);
+        assert_eq!(data.velocity, 30);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::level::Level;
+
+pub const DIFFICULTY_TICKS: u32 = 150; //生存玩法每隔这么多步难度加一级
+pub const SURVIVAL_MIN_VELOCITY: i32 = 40; //生存玩法加速到的最小移动间隔(毫秒),本来就更快的地图不再加速
+pub const SPEED_STEP: i32 = 20; //休闲玩法每按一次加速或减速键改变的毫秒数
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WallRule {
+    WrapPenalty, //穿墙到另一侧，生命值减一
+    Solid, //撞墙游戏结束
+    FreeWrap //自由穿墙，没有惩罚
+}
+
+impl WallRule {
+    pub fn parse(text: &str) -> Option<WallRule> {
+        match text {
+            "wrap" => Some(WallRule::WrapPenalty),
+            "solid" => Some(WallRule::Solid),
+            "free" => Some(WallRule::FreeWrap),
+            _ => None
+        }
+    }
+
+    pub fn key(&self) -> &'static str {//写入地图文件时使用的名字
+        match self {
+            WallRule::WrapPenalty => "wrap",
+            WallRule::Solid => "solid",
+            WallRule::FreeWrap => "free"
+        }
+    }
+
+    pub fn name(&self) -> &'static str {//界面上显示的名字
+        match self {
+            WallRule::WrapPenalty => "穿墙扣血",
+            WallRule::Solid => "撞墙结束",
+            WallRule::FreeWrap => "自由穿墙"
+        }
+    }
+
+    pub fn next(&self) -> WallRule {
+        match self {
+            WallRule::WrapPenalty => WallRule::Solid,
+            WallRule::Solid => WallRule::FreeWrap,
+            WallRule::FreeWrap => WallRule::WrapPenalty
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Variant {
+    Classic, //达到关卡目标过关
+    TimeAttack(i64), //限时(秒),时间到之前吃到尽量多的食物
+    Survival, //障碍物越来越多、速度越来越快,得分是存活的步数
+    Zen //休闲:不会因撞墙、撞到其他蛇和咬到自己而受伤,没有障碍物,速度可以随时调整
+}
+
+impl Variant {
+    pub fn parse(text: &str) -> Option<Variant> {
+        match text {
+            "classic" => Some(Variant::Classic),
+            "survival" => Some(Variant::Survival),
+            "zen" => Some(Variant::Zen),
+            _ if text.starts_with("time-") => text[5..].parse().ok().filter(|seconds| *seconds > 0).map(Variant::TimeAttack),
+            _ => None
+        }
+    }
+
+    pub fn key(&self) -> String {//与parse对应的名字
+        match self {
+            Variant::Classic => String::from("classic"),
+            Variant::TimeAttack(seconds) => format!("time-{}", seconds),
+            Variant::Survival => String::from("survival"),
+            Variant::Zen => String::from("zen")
+        }
+    }
+
+    pub fn name(&self) -> String {//界面上显示的名字
+        match self {
+            Variant::Classic => String::from("经典"),
+            Variant::TimeAttack(seconds) => format!("限时{}秒", seconds),
+            Variant::Survival => String::from("生存"),
+            Variant::Zen => String::from("休闲")
+        }
+    }
+
+    pub fn next(&self) -> Variant {
+        match self {
+            Variant::Classic => Variant::TimeAttack(60),
+            Variant::TimeAttack(60) => Variant::TimeAttack(120),
+            Variant::TimeAttack(_) => Variant::Survival,
+            Variant::Survival => Variant::Zen,
+            Variant::Zen => Variant::Classic
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Ruleset {
+    pub wall: WallRule, //撞到四周隔墙时的规则
+    pub variant: Variant //玩法,双人对战时只能是经典玩法
+}
+
+impl Ruleset {
+    pub fn classic() -> Ruleset {
+        Ruleset {
+            wall: WallRule::WrapPenalty,
+            variant: Variant::Classic
+        }
+    }
+
+    pub fn time_left(&self, t1: i64) -> Option<i64> {//限时玩法剩余的毫秒数
+        match self.variant {
+            Variant::TimeAttack(seconds) => Some((seconds * 1000 - t1).max(0)),
+            _ => None
+        }
+    }
+
+    pub fn difficulty(&self, tick: u32) -> u32 {//生存玩法的难度等级,其他玩法为0
+        if self.variant == Variant::Survival { tick / DIFFICULTY_TICKS } else { 0 }
+    }
+
+    pub fn wall_for(&self, level: &Level) -> WallRule {//关卡中设置的规则优先,休闲玩法总是自由穿墙
+        if self.variant == Variant::Zen {
+            return WallRule::FreeWrap;
+        }
+        level.wall.unwrap_or(self.wall)
+    }
+
+    pub fn harmless(&self) -> bool {//休闲玩法不出现障碍物等有伤害的物品,也不显示生命值
+        self.variant == Variant::Zen
+    }
+}
+
+pub fn harder(value: u32, difficulty: u32) -> u32 {//每升一级,障碍物出现的概率和数量上限增加一半
+    (value as u64 + value as u64 * difficulty as u64 / 2).min(u32::max_value() as u64) as u32 //很长的一局也不会溢出
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum GameMode {
+    Single, //单人游戏
+    Versus, //双人对战,玩家1用方向键,玩家2用WASD键
+    Coop //双人合作,两条蛇共享生命值和得分
+}
+
+impl GameMode {
+    pub fn parse(text: &str) -> Option<GameMode> {
+        match text {
+            "single" => Some(GameMode::Single),
+            "versus" => Some(GameMode::Versus),
+            "coop" => Some(GameMode::Coop),
+            _ => None
+        }
+    }
+
+    pub fn key(&self) -> &'static str {//与parse对应的名字
+        match self {
+            GameMode::Single => "single",
+            GameMode::Versus => "versus",
+            GameMode::Coop => "coop"
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Single => "单人",
+            GameMode::Versus => "双人对战",
+            GameMode::Coop => "双人合作"
+        }
+    }
+
+    pub fn next(&self) -> GameMode {
+        match self {
+            GameMode::Single => GameMode::Versus,
+            GameMode::Versus => GameMode::Coop,
+            GameMode::Coop => GameMode::Single
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_wall_rules() {
+        for wall in [WallRule::WrapPenalty, WallRule::Solid, WallRule::FreeWrap].iter() {
+            assert_eq!(WallRule::parse(wall.key()), Some(*wall));
+            assert_eq!(wall.next().next().next(), *wall);
+        }
+        assert_eq!(WallRule::parse("bounce"), None);
+    }
+
+    #[test]
+    fn level_wall_overrides_ruleset() {
+        let mut level = Level::blank();
+        let rules = Ruleset { wall: WallRule::Solid, variant: Variant::Classic };
+        assert_eq!(rules.wall_for(&level), WallRule::Solid);
+        level.wall = Some(WallRule::FreeWrap);
+        assert_eq!(rules.wall_for(&level), WallRule::FreeWrap);
+    }
+
+    #[test]
+    fn parses_variants() {
+        for variant in [Variant::Classic, Variant::TimeAttack(90), Variant::Survival, Variant::Zen].iter() {
+            assert_eq!(Variant::parse(&variant.key()), Some(*variant));
+        }
+        assert_eq!(Variant::parse("time-0"), None);
+        assert_eq!(Variant::parse("time-x"), None);
+    }
+
+    #[test]
+    fn survival_gets_harder_without_overflow() {
+        let survival = Ruleset { wall: WallRule::WrapPenalty, variant: Variant::Survival };
+        assert_eq!(survival.difficulty(DIFFICULTY_TICKS - 1), 0);
+        assert_eq!(survival.difficulty(DIFFICULTY_TICKS), 1);
+        assert_eq!(Ruleset::classic().difficulty(DIFFICULTY_TICKS * 10), 0);
+        assert_eq!(harder(80, 0), 80);
+        assert_eq!(harder(80, 1), 120);
+        assert_eq!(harder(80, 2), 160);
+        assert_eq!(harder(80, 1000), 40_080);
+        assert_eq!(harder(1000, survival.difficulty(u32::max_value())), u32::max_value());
+        assert_eq!(harder(u32::max_value(), 3), u32::max_value());
+    }
+}
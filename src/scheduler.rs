@@ -0,0 +1,70 @@
+use rand::Rng;
+
+use crate::items::ItemKind;
+use crate::rules::harder;
+
+#[derive(Clone)]
+pub struct Scheduler {
+    next: Vec<u32> //每种物品下一次按间隔出现的步数
+}
+
+impl Scheduler {
+    pub fn new(items: &[ItemKind]) -> Scheduler {
+        Scheduler {
+            next: items.iter().map(|item| item.interval).collect()
+        }
+    }
+
+    pub fn due<R: Rng>(&mut self, tick: u32, items: &[ItemKind], difficulty: u32, rng: &mut R) -> Vec<usize> {//返回这一步应该出现的物品,难度越高伤害物品越多
+        let mut kinds = Vec::new();
+        for kind in 0..items.len() {
+            let item = &items[kind];
+            let (interval, chance) = if item.damages() {
+                ((item.interval as u64 * 2 / (2 + difficulty as u64)) as u32, harder(item.chance, difficulty))
+            } else {
+                (item.interval, item.chance)
+            };
+            if item.interval > 0 && tick >= self.next[kind] { //按固定间隔出现
+                self.next[kind] = tick + interval.max(1);
+                kinds.push(kind);
+            } else if chance > 0 && rng.gen_range(0, 1000) < chance { //按概率出现
+                kinds.push(kind);
+            }
+        }
+        kinds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::builtin_items;
+    use crate::seed_rng;
+
+    #[test]
+    fn spawns_by_interval_and_chance() {
+        let mut items = builtin_items();
+        items[0].interval = 5;
+        items[1].chance = 1000;
+        items[2].chance = 0;
+        let mut scheduler = Scheduler::new(&items);
+        let mut rng = seed_rng(1);
+        let due: Vec<Vec<usize>> = (0..20).map(|tick| scheduler.due(tick, &items, 0, &mut rng)).collect();
+        let food: Vec<usize> = (0..20).filter(|tick| due[*tick].contains(&0)).collect();
+        assert_eq!(food, vec![5, 10, 15]);
+        assert!(due.iter().all(|kinds| kinds.contains(&1))); //千分之一千每一步都出现
+        assert!(due.iter().all(|kinds| !kinds.contains(&2)));
+    }
+
+    #[test]
+    fn harmful_items_come_faster_when_harder() {
+        let mut items = builtin_items();
+        items[2].interval = 10;
+        items[2].chance = 0;
+        let mut scheduler = Scheduler::new(&items);
+        let mut rng = seed_rng(1);
+        let due: Vec<u32> = (0..30).filter(|tick| scheduler.due(*tick, &items, 3, &mut rng).contains(&2)).collect();
+        assert_eq!(due, vec![10, 14, 18, 22, 26]); //难度3时间隔变成10*2/5
+        assert!(!scheduler.due(30, &items, u32::max_value(), &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::PathBuf;
+
+pub const STAR_BONUS: i32 = 20; //吃到能恢复生命值的物品的奖励分
+pub const SURVIVAL_TICKS: u32 = 10; //每存活这么多步加一分
+pub const TABLE_SIZE: usize = 10; //每张排行榜保留的记录数
+pub const COMBO_TICKS: u32 = 30; //在这么多步内吃到下一个食物即可连击
+pub const MAX_COMBO: i32 = 8; //连击倍数上限
+
+pub fn food_points(velocity: i32, length: i16, grow: i16) -> i32 {//吃到一个食物的得分
+    let speed = 6000 / velocity.max(20); //速度系数(十倍),第一关为10,第六关为100
+    let size = 10 + length as i32 / 5; //长度系数(十倍),蛇越长得分越高
+    (10 * grow as i32 * speed * size / 100).max(1)
+}
+
+#[derive(Clone)]
+pub struct ScoreEntry {
+    pub name: String, //玩家名字
+    pub score: i32, //得分
+    pub length: i16, //结束时的长度
+    pub food: i16, //吃到的食物数
+    pub seconds: i64 //用时
+}
+
+pub struct HighScores {
+    path: PathBuf, //排行榜文件
+    tables: Vec<(String, Vec<ScoreEntry>)> //每个关卡一张排行榜
+}
+
+impl HighScores {
+    pub fn load(path: PathBuf) -> HighScores {//文件不存在或无法解析的行会被忽略
+        let mut scores = HighScores { path: path, tables: Vec::new() };
+        let text = fs::read_to_string(&scores.path).unwrap_or_default();
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 6 {
+                continue;
+            }
+            let entry = ScoreEntry {
+                name: fields[1].to_string(),
+                score: fields[2].parse().unwrap_or(0),
+                length: fields[3].parse().unwrap_or(0),
+                food: fields[4].parse().unwrap_or(0),
+                seconds: fields[5].parse().unwrap_or(0)
+            };
+            scores.insert_entry(fields[0], entry);
+        }
+        scores
+    }
+
+    pub fn table(&self, key: &str) -> &[ScoreEntry] {
+        match self.tables.iter().find(|(name, _)| name == key) {
+            Some((_, entries)) => entries,
+            None => &[]
+        }
+    }
+
+    pub fn best(&self, key: &str) -> i32 {
+        self.table(key).first().map_or(0, |entry| entry.score)
+    }
+
+    pub fn qualifies(&self, key: &str, score: i32) -> bool {//得分能否进入排行榜
+        let table = self.table(key);
+        score > 0 && (table.len() < TABLE_SIZE || score > table[table.len() - 1].score)
+    }
+
+    pub fn insert(&mut self, key: &str, entry: ScoreEntry) -> Result<(), String> {
+        self.insert_entry(key, entry);
+        self.save()
+    }
+
+    fn insert_entry(&mut self, key: &str, entry: ScoreEntry) {
+        let index = match self.tables.iter().position(|(name, _)| name == key) {
+            Some(index) => index,
+            None => {
+                self.tables.push((key.to_string(), Vec::new()));
+                self.tables.len() - 1
+            }
+        };
+        let entries = &mut self.tables[index].1;
+        let position = entries.iter().position(|old| entry.score > old.score).unwrap_or(entries.len());
+        entries.insert(position, entry);
+        entries.truncate(TABLE_SIZE);
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut text = String::new();
+        for (key, entries) in self.tables.iter() {
+            for entry in entries {
+                text += &format!("{}\t{}\t{}\t{}\t{}\t{}\n", key, entry.name.replace('\t', " "),
+                    entry.score, entry.length, entry.food, entry.seconds);
+            }
+        }
+        fs::write(&self.path, text).map_err(|e| format!("无法保存排行榜：{}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: i32) -> ScoreEntry {
+        ScoreEntry { name: name.to_string(), score: score, length: 10, food: 5, seconds: 30 }
+    }
+
+    #[test]
+    fn weights_food_by_speed_and_length() {
+        assert_eq!(food_points(600, 0, 1), 10); //第一关
+        assert_eq!(food_points(100, 0, 1), 60);
+        assert_eq!(food_points(600, 20, 1), 14);
+        assert_eq!(food_points(600, 0, 3), 30);
+        assert_eq!(food_points(0, 0, 1), food_points(20, 0, 1)); //不会除以0
+        assert_eq!(food_points(1000, 0, 1), 6);
+    }
+
+    #[test]
+    fn keeps_best_scores_per_table() {
+        let path = std::env::temp_dir().join(format!("snake-rust-scores-{}.txt", std::process::id()));
+        let mut scores = HighScores::load(path.clone());
+        assert!(!scores.qualifies("level-1", 0));
+        for i in 1..=TABLE_SIZE as i32 + 2 {
+            assert!(scores.qualifies("level-1", i * 10));
+            scores.insert("level-1", entry("a\tb", i * 10)).unwrap();
+        }
+        scores.insert("map-x", entry("c", 5)).unwrap();
+        let loaded = HighScores::load(path.clone());
+        let _ = fs::remove_file(&path);
+        let table = loaded.table("level-1");
+        assert_eq!(table.len(), TABLE_SIZE);
+        assert_eq!(table.iter().map(|entry| entry.score).collect::<Vec<_>>(), (3..=12).rev().map(|i| i * 10).collect::<Vec<_>>());
+        assert_eq!(table[0].name, "a b"); //名字中的制表符换成空格
+        assert_eq!(loaded.best("map-x"), 5);
+        assert_eq!(loaded.best("level-2"), 0);
+        assert!(!loaded.qualifies("level-1", 30));
+        assert!(loaded.qualifies("level-1", 31));
+    }
+}
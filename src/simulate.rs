@@ -0,0 +1,173 @@
+use std::time::Instant;
+
+use crate::ai::AgentKind;
+use crate::items::ItemKind;
+use crate::level::Level;
+use crate::rules::WallRule;
+use crate::{cause_name, fit, game_outcome, load_stage, new_game, reached_goal, step, ColorPairs, GameData};
+
+pub struct Options {
+    pub agent: AgentKind, //玩家1使用的策略
+    pub games: u64, //模拟的局数
+    pub seed: u64, //第一局的种子,之后每局加一
+    pub stage: Level, //关卡
+    pub wall: Option<WallRule>, //撞墙规则,None时使用默认规则
+    pub bots: usize, //电脑对手的数量
+    pub bot_agent: AgentKind, //电脑对手使用的策略
+    pub hp: i8, //开局的生命值
+    pub max_ticks: u32 //每局最多的步数,超过时算作超时
+}
+
+struct Record {
+    length: i16, //结束时的长度
+    food: i16, //吃到的食物数
+    ticks: u32, //存活的步数
+    end: String //结束的原因
+}
+
+const USAGE: &str = "用法：snake-rust simulate [--agent 策略] [--games 局数] [--seed 种子] [--level 1-6 | --map 地图文件]
+                    [--wall wrap|solid|free] [--bots 数量] [--bot-agent 策略] [--hp 生命值] [--max-ticks 步数]
+策略：random、greedy、bfs、astar、hamilton";
+
+pub fn run_simulate(args: &[String], items: Vec<ItemKind>) -> Result<(), String> {//不使用终端批量模拟游戏并输出统计
+    let options = parse_options(args).map_err(|message| format!("{}\n{}", message, USAGE))?;
+    let end = options.seed.checked_add(options.games).ok_or(format!("种子加局数超出范围\n{}", USAGE))?;
+    let mut data = headless_game(items, &options);
+    data.autopilot = Some(options.agent);
+    let start = Instant::now();
+    let mut records = Vec::new();
+    for seed in options.seed..end {
+        records.push(play(&mut data, &options, seed));
+    }
+    report(&data, &options, &records, start.elapsed().as_secs_f64());
+    Ok(())
+}
+
+pub fn headless_game(items: Vec<ItemKind>, options: &Options) -> GameData {//按选项创建不使用终端的游戏
+    configure(new_game(items, ColorPairs::default()), options)
+}
+
+pub fn configure(mut data: GameData, options: &Options) -> GameData {//按选项设置电脑对手和撞墙规则
+    data.bots = options.bots;
+    data.bot_agent = options.bot_agent;
+    if let Some(wall) = options.wall {
+        data.rules.wall = wall;
+    }
+    data
+}
+
+pub fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        agent: AgentKind::Greedy,
+        games: 1000,
+        seed: 1,
+        stage: Level::builtin(1),
+        wall: None,
+        bots: 0,
+        bot_agent: AgentKind::Greedy,
+        hp: 6,
+        max_ticks: 20000
+    };
+    let mut i = 0;
+    while i < args.len() {
+        let value = match args.get(i + 1) {
+            Some(value) => value.as_str(),
+            None => return Err(format!("{}缺少参数", args[i]))
+        };
+        let number = || value.parse::<u64>().map_err(|_| format!("{}必须是数字", args[i]));
+        match args[i].as_str() {
+            "--agent" => options.agent = AgentKind::parse(value).ok_or(format!("未知的策略：{}", value))?,
+            "--bot-agent" => options.bot_agent = AgentKind::parse(value).ok_or(format!("未知的策略：{}", value))?,
+            "--games" => options.games = number()?,
+            "--seed" => options.seed = number()?,
+            "--level" => match number()? {
+                n @ 1..=6 => options.stage = Level::builtin(n as i8),
+                _ => return Err(String::from("关数必须是1-6"))
+            },
+            "--map" => options.stage = Level::load(value)?,
+            "--wall" => options.wall = Some(WallRule::parse(value).ok_or(format!("未知的撞墙规则：{}", value))?),
+            "--bots" => options.bots = number()?.min(3) as usize,
+            "--hp" => options.hp = number()?.max(1).min(9) as i8,
+            "--max-ticks" => options.max_ticks = number()?.max(1).min(u32::max_value() as u64) as u32,
+            _ => return Err(format!("未知的参数：{}", args[i]))
+        }
+        i += 2;
+    }
+    Ok(options)
+}
+
+pub fn start(data: &mut GameData, options: &Options, seed: u64) {//用指定的种子开始一局
+    data.seed = seed;
+    data.stage = options.stage.clone();
+    data.level = data.stage.number;
+    data.start_hp = options.hp;
+    load_stage(data);
+}
+
+fn play(data: &mut GameData, options: &Options, seed: u64) -> Record {//用指定的种子玩一局
+    start(data, options, seed);
+    while data.tick < options.max_ticks {
+        step(data);
+        if game_outcome(data).is_some() {
+            break;
+        }
+    }
+    let end = if !data.snakes[0].alive {
+        data.stats[0].last_hit.map_or(String::from("未知"), |cause| cause_name(data, cause))
+    } else if reached_goal(data, &data.snakes[0]) {
+        String::from("过关")
+    } else {
+        String::from("超时")
+    };
+    Record {
+        length: data.snakes[0].length - 1,
+        food: data.stats[0].food,
+        ticks: data.tick,
+        end: end
+    }
+}
+
+fn summary(mut values: Vec<f64>) -> (f64, f64, f64) {//平均数、中位数和最大值
+    if values.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let middle = values.len() / 2;
+    let median = if values.len() % 2 == 0 { (values[middle - 1] + values[middle]) / 2.0 } else { values[middle] };
+    (mean, median, values[values.len() - 1])
+}
+
+fn report(data: &GameData, options: &Options, records: &[Record], seconds: f64) {
+    println!("策略：{}  局数：{}  种子：{}-{}", options.agent.name(), records.len(),
+        options.seed, options.seed.saturating_add(options.games.max(1) - 1));
+    println!("关卡：{}  撞墙规则：{}  电脑对手：{}条({})", data.stage.name, data.rules.wall_for(&data.stage).name(),
+        options.bots, options.bot_agent.name());
+    println!();
+    println!("{}{}{}{}", fit("", 10), fit("      平均", 10), fit("    中位数", 10), fit("      最大", 10));
+    let rows: [(&str, Vec<f64>); 3] = [
+        ("长度", records.iter().map(|record| record.length as f64).collect()),
+        ("食物", records.iter().map(|record| record.food as f64).collect()),
+        ("存活步数", records.iter().map(|record| record.ticks as f64).collect())
+    ];
+    for (name, values) in rows.iter() {
+        let (mean, median, max) = summary(values.clone());
+        println!("{}{:>10.1}{:>10.1}{:>10.0}", fit(name, 10), mean, median, max);
+    }
+    println!();
+    println!("结束原因：");
+    let mut ends: Vec<(&str, usize)> = Vec::new();
+    for record in records {
+        match ends.iter().position(|(end, _)| *end == record.end) {
+            Some(index) => ends[index].1 += 1,
+            None => ends.push((&record.end, 1))
+        }
+    }
+    ends.sort_by(|a, b| b.1.cmp(&a.1));
+    for (end, count) in ends {
+        println!("  {}{:>8}{:>8.1}%", fit(end, 20), count, count as f64 * 100.0 / records.len() as f64);
+    }
+    let ticks: u64 = records.iter().map(|record| record.ticks as u64).sum();
+    println!();
+    println!("共{}步，用时{:.2}秒，每秒{:.0}步", ticks, seconds, ticks as f64 / seconds.max(0.000_001));
+}
@@ -0,0 +1,343 @@
+use crate::items::{parse_effect, ItemKind};
+use crate::json::Json;
+use crate::level::{spawn_char, spawn_direction, Level, MAP_HEIGHT, MAP_WIDTH};
+use crate::rules::{GameMode, Variant, WallRule};
+use crate::{ColorName, Controller, Direction, GameData, ItemType, Snake, SnakeData, Stats};
+
+pub struct Mirror {//上一次发送出去的状态,用来找出这一步的变化
+    map: [[ItemType; MAP_HEIGHT]; MAP_WIDTH],
+    expires: [[u32; MAP_HEIGHT]; MAP_WIDTH],
+    snakes: Vec<Json>,
+    stats: Vec<Json>
+}
+
+impl Mirror {
+    pub fn new(data: &GameData) -> Mirror {
+        Mirror {
+            map: data.map,
+            expires: data.expires,
+            snakes: data.snakes.iter().map(encode_snake).collect(),
+            stats: data.stats.iter().map(encode_stats).collect()
+        }
+    }
+
+    pub fn diff(&mut self, data: &GameData) -> Json {//只包含变化了的格子、蛇和生命值得分
+        let mut cells = Vec::new();
+        for x in 0..MAP_WIDTH {
+            for y in 0..MAP_HEIGHT {
+                if data.map[x][y] != self.map[x][y] || data.expires[x][y] != self.expires[x][y] {
+                    cells.push(number_array(&[x as f64, y as f64, cell_code(data.map[x][y]) as f64, data.expires[x][y] as f64]));
+                }
+            }
+        }
+        let snakes = changed(&mut self.snakes, data.snakes.iter().map(encode_snake).collect());
+        let stats = changed(&mut self.stats, data.stats.iter().map(encode_stats).collect());
+        self.map = data.map;
+        self.expires = data.expires;
+        object(vec![
+            ("type", Json::String(String::from("tick"))),
+            ("tick", Json::Number(data.tick as f64)),
+            ("t1", Json::Number(data.t1 as f64)),
+            ("velocity", Json::Number(data.velocity as f64)),
+            ("cells", Json::Array(cells)),
+            ("snakes", snakes),
+            ("stats", stats)
+        ])
+    }
+}
+
+fn changed(old: &mut Vec<Json>, new: Vec<Json>) -> Json {//返回[[位置, 新的值], ...]
+    let mut result = Vec::new();
+    for (i, value) in new.iter().enumerate() {
+        if old.get(i) != Some(value) {
+            result.push(Json::Array(vec![Json::Number(i as f64), value.clone()]));
+        }
+    }
+    *old = new;
+    Json::Array(result)
+}
+
+pub fn snapshot(data: &GameData) -> Json {//完整的游戏状态,每局开始时发给所有的客户端
+    let mut expires = Vec::new();
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if data.expires[x][y] > 0 {
+                expires.push(number_array(&[x as f64, y as f64, data.expires[x][y] as f64]));
+            }
+        }
+    }
+    let map = (0..MAP_HEIGHT).map(|y| {
+        Json::Array((0..MAP_WIDTH).map(|x| Json::Number(cell_code(data.map[x][y]) as f64)).collect())
+    }).collect();
+    let items = data.items.iter().map(|item| object(vec![
+        ("name", Json::String(item.name.clone())),
+        ("glyph", Json::String(item.glyph.clone())),
+        ("color", Json::String(String::from(item.color.key()))),
+        ("effects", Json::String(item.effects.iter().map(|effect| effect.to_text()).collect::<Vec<String>>().join(",")))
+    ])).collect();
+    object(vec![
+        ("type", Json::String(String::from("snapshot"))),
+        ("tick", Json::Number(data.tick as f64)),
+        ("t1", Json::Number(data.t1 as f64)),
+        ("velocity", Json::Number(data.velocity as f64)),
+        ("level", Json::Number(data.level as f64)),
+        ("stage", Json::String(data.stage.to_text())),
+        ("wall", Json::String(String::from(data.rules.wall.key()))),
+        ("variant", Json::String(data.rules.variant.key())),
+        ("mode", Json::String(String::from(data.mode.key()))),
+        ("start_hp", Json::Number(data.start_hp as f64)),
+        ("items", Json::Array(items)),
+        ("map", Json::Array(map)),
+        ("expires", Json::Array(expires)),
+        ("snakes", Json::Array(data.snakes.iter().map(encode_snake).collect())),
+        ("stats", Json::Array(data.stats.iter().map(encode_stats).collect()))
+    ])
+}
+
+pub fn apply(data: &mut GameData, message: &Json) -> Result<(), String> {//把收到的完整状态或变化应用到本地的游戏数据
+    match message.get("type").and_then(Json::as_str) {
+        Some("snapshot") => {
+            let mut items = Vec::new();
+            for item in array(message, "items")? {
+                let mut effects = Vec::new();
+                for effect in text(item, "effects")?.split(',').filter(|effect| !effect.is_empty()) {
+                    effects.push(parse_effect(effect)?);
+                }
+                items.push(ItemKind {
+                    name: String::from(text(item, "name")?),
+                    glyph: String::from(text(item, "glyph")?),
+                    color: ColorName::parse(text(item, "color")?).ok_or("未知的颜色")?,
+                    chance: 0,
+                    interval: 0,
+                    keep: 0,
+                    max: 0,
+                    lifetime: 0,
+                    effects: effects
+                });
+            }
+            data.items = items;
+            data.stage = Level::parse(text(message, "stage")?)?;
+            data.level = number(message, "level")? as i8;
+            data.stage.number = data.level;
+            data.rules.wall = WallRule::parse(text(message, "wall")?).ok_or("未知的撞墙规则")?;
+            data.rules.variant = match message.get("variant").and_then(Json::as_str) { //旧版本的服务器没有发送玩法
+                Some(key) => Variant::parse(key).ok_or("未知的玩法")?,
+                None => Variant::Classic
+            };
+            data.mode = GameMode::parse(text(message, "mode")?).ok_or("未知的游戏模式")?;
+            data.start_hp = number(message, "start_hp")? as i8;
+            let map = array(message, "map")?;
+            for y in 0..MAP_HEIGHT {
+                let row = match map.get(y) {
+                    Some(Json::Array(row)) if row.len() == MAP_WIDTH => row,
+                    _ => return Err(String::from("地图大小不正确"))
+                };
+                for x in 0..MAP_WIDTH {
+                    data.map[x][y] = decode_cell(data, row[x].as_f64().ok_or("地图格子必须是数字")?)?;
+                }
+            }
+            data.expires = [[0; MAP_HEIGHT]; MAP_WIDTH];
+            for cell in array(message, "expires")? {
+                let values = numbers(cell, 3)?;
+                let (x, y) = (values[0] as usize, values[1] as usize);
+                if x >= MAP_WIDTH || y >= MAP_HEIGHT {
+                    return Err(String::from("格子超出地图"));
+                }
+                data.expires[x][y] = values[2] as u32;
+            }
+            data.snakes.clear();
+            for snake in array(message, "snakes")? {
+                data.snakes.push(decode_snake(snake)?);
+            }
+            data.stats.clear();
+            for stats in array(message, "stats")? {
+                data.stats.push(decode_stats(stats)?);
+            }
+        },
+        Some("tick") => {
+            for cell in array(message, "cells")? {
+                let values = numbers(cell, 4)?;
+                let (x, y) = (values[0] as usize, values[1] as usize);
+                if x >= MAP_WIDTH || y >= MAP_HEIGHT {
+                    return Err(String::from("格子超出地图"));
+                }
+                data.map[x][y] = decode_cell(data, values[2])?;
+                data.expires[x][y] = values[3] as u32;
+            }
+            for entry in array(message, "snakes")? {
+                let (i, snake) = indexed(entry)?;
+                let snake = decode_snake(snake)?;
+                if i < data.snakes.len() {
+                    data.snakes[i] = snake;
+                } else {
+                    data.snakes.push(snake);
+                }
+            }
+            for entry in array(message, "stats")? {
+                let (i, stats) = indexed(entry)?;
+                let stats = decode_stats(stats)?;
+                if i < data.stats.len() {
+                    data.stats[i] = stats;
+                } else {
+                    data.stats.push(stats);
+                }
+            }
+        },
+        _ => return Err(String::from("不是游戏状态"))
+    }
+    if data.snakes.iter().any(|snake| snake.stats >= data.stats.len()) {
+        return Err(String::from("蛇的生命值记录不存在"));
+    }
+    data.tick = number(message, "tick")? as u32;
+    data.t1 = number(message, "t1")? as i64;
+    data.velocity = number(message, "velocity")? as i32;
+    Ok(())
+}
+
+pub fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right"
+    }
+}
+
+pub fn parse_direction(name: &str) -> Option<Direction> {
+    match name {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None
+    }
+}
+
+pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(key, value)| (String::from(key), value)).collect())
+}
+
+fn number_array(values: &[f64]) -> Json {
+    Json::Array(values.iter().map(|value| Json::Number(*value)).collect())
+}
+
+fn cell_code(item: ItemType) -> usize {//0是空地,1是墙,物品是在物品表中的位置加2
+    match item {
+        ItemType::None => 0,
+        ItemType::Wall => 1,
+        ItemType::Item(kind) => kind + 2
+    }
+}
+
+fn decode_cell(data: &GameData, code: f64) -> Result<ItemType, String> {
+    match code as usize {
+        0 => Ok(ItemType::None),
+        1 => Ok(ItemType::Wall),
+        code if code - 2 < data.items.len() => Ok(ItemType::Item(code - 2)),
+        _ => Err(String::from("未知的物品"))
+    }
+}
+
+fn encode_snake(snake: &Snake) -> Json {//蛇身包括最后记录蛇尾的一节
+    let body = snake.body[..snake.length as usize].iter().map(|segment| Json::Array(vec![
+        Json::Number(segment.x as f64),
+        Json::Number(segment.y as f64),
+        Json::String(spawn_char(segment.direction).to_string())
+    ])).collect();
+    object(vec![
+        ("body", Json::Array(body)),
+        ("stats", Json::Number(snake.stats as f64)),
+        ("head", Json::String(String::from(snake.head_color.key()))),
+        ("color", Json::String(String::from(snake.body_color.key()))),
+        ("player", Json::Bool(snake.player)),
+        ("alive", Json::Bool(snake.alive))
+    ])
+}
+
+fn decode_snake(json: &Json) -> Result<Snake, String> {
+    let segments = array(json, "body")?;
+    if segments.len() < 2 || segments.len() > 81 {
+        return Err(String::from("蛇身长度不正确"));
+    }
+    let mut body = [SnakeData { x: 2, y: 1, direction: Direction::Right }; 81];
+    for (i, segment) in segments.iter().enumerate() {
+        let parts = match segment {
+            Json::Array(parts) if parts.len() == 3 => parts,
+            _ => return Err(String::from("蛇身格式不正确"))
+        };
+        body[i] = SnakeData {
+            x: parts[0].as_f64().ok_or("蛇身坐标必须是数字")? as i16,
+            y: parts[1].as_f64().ok_or("蛇身坐标必须是数字")? as i16,
+            direction: parts[2].as_str().and_then(|text| text.chars().next()).and_then(spawn_direction).ok_or("未知的方向")?
+        };
+        if body[i].x < 2 || body[i].x > 52 || body[i].y < 1 || body[i].y > 22 {
+            return Err(String::from("蛇身超出地图"));
+        }
+    }
+    Ok(Snake {
+        body: body,
+        length: segments.len() as i16,
+        stats: number(json, "stats")? as usize,
+        head_color: ColorName::parse(text(json, "head")?).ok_or("未知的颜色")?,
+        body_color: ColorName::parse(text(json, "color")?).ok_or("未知的颜色")?,
+        controller: Controller::Remote,
+        turns: Vec::new(),
+        player: flag(json, "player")?,
+        alive: flag(json, "alive")?
+    })
+}
+
+fn encode_stats(stats: &Stats) -> Json {
+    object(vec![
+        ("hp", Json::Number(stats.hp as f64)),
+        ("food", Json::Number(stats.food as f64)),
+        ("score", Json::Number(stats.score as f64)),
+        ("combo", Json::Number(stats.combo as f64)),
+        ("combo_tick", stats.combo_tick.map_or(Json::Null, |tick| Json::Number(tick as f64)))
+    ])
+}
+
+fn decode_stats(json: &Json) -> Result<Stats, String> {
+    let mut stats = Stats::new(number(json, "hp")? as i8);
+    stats.food = number(json, "food")? as i16;
+    stats.score = number(json, "score")? as i32;
+    stats.combo = number(json, "combo")? as i32;
+    stats.combo_tick = json.get("combo_tick").and_then(Json::as_f64).map(|tick| tick as u32); //null表示还没吃到食物
+    Ok(stats)
+}
+
+fn number(json: &Json, key: &str) -> Result<f64, String> {
+    json.get(key).and_then(Json::as_f64).ok_or(format!("缺少数字{}", key))
+}
+
+fn text<'a>(json: &'a Json, key: &str) -> Result<&'a str, String> {
+    json.get(key).and_then(Json::as_str).ok_or(format!("缺少字符串{}", key))
+}
+
+fn flag(json: &Json, key: &str) -> Result<bool, String> {
+    match json.get(key) {
+        Some(Json::Bool(value)) => Ok(*value),
+        _ => Err(format!("缺少{}", key))
+    }
+}
+
+fn array<'a>(json: &'a Json, key: &str) -> Result<&'a Vec<Json>, String> {
+    match json.get(key) {
+        Some(Json::Array(items)) => Ok(items),
+        _ => Err(format!("缺少数组{}", key))
+    }
+}
+
+fn numbers(json: &Json, count: usize) -> Result<Vec<f64>, String> {//长度为count的数字数组
+    match json {
+        Json::Array(items) if items.len() == count => items.iter().map(|item| item.as_f64().ok_or(String::from("必须是数字"))).collect(),
+        _ => Err(format!("应该是{}个数字", count))
+    }
+}
+
+fn indexed(json: &Json) -> Result<(usize, &Json), String> {//[位置, 值]
+    match json {
+        Json::Array(items) if items.len() == 2 => Ok((items[0].as_f64().ok_or("位置必须是数字")? as usize, &items[1])),
+        _ => Err(String::from("格式应为[位置, 值]"))
+    }
+}
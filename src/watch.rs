@@ -0,0 +1,222 @@
+#[cfg(unix)]
+use std::fs;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use ncursesw::*;
+
+use crate::json::Json;
+use crate::net::{self, notice};
+use crate::sync::{self, object, Mirror};
+use crate::{addstr, clear_screen, draw_board, draw_frame, move_cursor, set_color, set_cursor_visiable, update_data, GameData};
+
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200); //观众太慢时断开,不拖慢游戏
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, String) //退出时删除的套接字文件
+}
+
+pub struct Publisher {//把正在进行的游戏发给观众
+    listener: Listener,
+    spectators: Vec<Box<dyn Write>>,
+    mirror: Option<Mirror> //游戏没有开始时为None
+}
+
+#[cfg(unix)]
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = &self.listener {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn is_unix(address: &str) -> bool {//包含'/'的地址是Unix套接字的路径
+    address.contains('/')
+}
+
+#[cfg(not(unix))]
+const NO_UNIX_SOCKET: &str = "这个系统不支持Unix套接字,请使用端口";
+
+fn tcp_address(address: &str, host: &str) -> String {//只写端口时使用默认的主机
+    if address.contains(':') { address.to_string() } else { format!("{}:{}", host, address) }
+}
+
+impl Publisher {
+    pub fn bind(address: &str) -> Result<Publisher, String> {//地址可以是端口、主机:端口或者Unix套接字的路径
+        let listener = if is_unix(address) {
+            #[cfg(not(unix))]
+            return Err(String::from(NO_UNIX_SOCKET));
+            #[cfg(unix)]
+            {
+                let _ = fs::remove_file(address); //删除上次留下的套接字文件
+                let listener = UnixListener::bind(address).map_err(|e| format!("无法监听{}：{}", address, e))?;
+                listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+                Listener::Unix(listener, address.to_string())
+            }
+        } else {
+            let address = tcp_address(address, "0.0.0.0");
+            let listener = TcpListener::bind(&address).map_err(|e| format!("无法监听{}：{}", address, e))?;
+            listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+            Listener::Tcp(listener)
+        };
+        Ok(Publisher { listener: listener, spectators: Vec::new(), mirror: None })
+    }
+
+    fn accept(&mut self) -> Vec<Box<dyn Write>> {//新连接的观众
+        let mut joined: Vec<Box<dyn Write>> = Vec::new();
+        match &self.listener {
+            Listener::Tcp(listener) => {
+                while let Ok((stream, _)) = listener.accept() {
+                    let _ = stream.set_nonblocking(false);
+                    let _ = stream.set_nodelay(true);
+                    let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+                    joined.push(Box::new(stream));
+                }
+            },
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                while let Ok((stream, _)) = listener.accept() {
+                    let _ = stream.set_nonblocking(false);
+                    let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+                    joined.push(Box::new(stream));
+                }
+            }
+        }
+        joined
+    }
+
+    fn send(&mut self, message: &Json) {//发送失败的观众直接去掉
+        let line = format!("{}\n", message);
+        let mut i = 0;
+        while i < self.spectators.len() {
+            if self.spectators[i].write_all(line.as_bytes()).is_err() {
+                self.spectators.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    pub fn restart(&mut self, data: &GameData) {//新的一局开始,所有的观众都收到完整的状态
+        let joined = self.accept();
+        self.spectators.extend(joined);
+        self.send(&sync::snapshot(data));
+        self.mirror = Some(Mirror::new(data));
+    }
+
+    pub fn frame(&mut self, data: &GameData) {//发送这一步的变化,中途加入的观众先收到完整的状态
+        if let Some(mirror) = self.mirror.as_mut() {
+            let diff = mirror.diff(data);
+            self.send(&diff);
+        }
+        let joined = self.accept();
+        if !joined.is_empty() {
+            let line = format!("{}\n", sync::snapshot(data));
+            for mut spectator in joined {
+                if spectator.write_all(line.as_bytes()).is_ok() {
+                    self.spectators.push(spectator);
+                }
+            }
+        }
+    }
+
+    pub fn over(&mut self, message: &str) {//游戏结束时显示的结果
+        self.send(&object(vec![("type", Json::String(String::from("over"))), ("message", Json::String(message.to_string()))]));
+    }
+
+    pub fn menu(&mut self) {//回到选关界面,观众等待下一局
+        self.mirror = None;
+        self.send(&object(vec![("type", Json::String(String::from("menu")))]));
+    }
+}
+
+pub fn publish<F: FnOnce(&mut Publisher, &GameData)>(data: &mut GameData, f: F) {//有观众时把游戏状态发给他们
+    if let Some(mut publisher) = data.spectators.take() {
+        f(&mut publisher, data);
+        data.spectators = Some(publisher);
+    }
+}
+
+pub fn run_watch(data: &mut GameData, address: &str) -> Result<(), String> {//连接到正在进行的游戏,只显示不操作
+    let (sender, receiver) = mpsc::channel();
+    if is_unix(address) {
+        #[cfg(not(unix))]
+        return Err(String::from(NO_UNIX_SOCKET));
+        #[cfg(unix)]
+        {
+            let stream = UnixStream::connect(address).map_err(|e| format!("无法连接{}：{}", address, e))?;
+            thread::spawn(move || net::read_messages(0, stream, sender));
+        }
+    } else {
+        let address = tcp_address(address, "127.0.0.1");
+        let stream = TcpStream::connect(&address).map_err(|e| format!("无法连接{}：{}", address, e))?;
+        thread::spawn(move || net::read_messages(0, stream, sender));
+    }
+    set_cursor_visiable(false);
+    draw_frame(data);
+    clear_screen();
+    notice(data, "已连接,等待游戏开始");
+    cbreak().unwrap();
+    let mut playing = false;
+    loop {
+        loop {
+            let message = match receiver.try_recv() {
+                Ok((_, Some(message))) => message,
+                Ok((_, None)) | Err(TryRecvError::Disconnected) => {
+                    notice(data, "游戏已经关闭");
+                    thread::sleep(Duration::from_secs(2));
+                    return Ok(());
+                },
+                Err(TryRecvError::Empty) => break
+            };
+            match message.get("type").and_then(Json::as_str) {
+                Some("snapshot") => {
+                    sync::apply(data, &message)?;
+                    playing = true;
+                    draw_frame(data); //单人和双人的信息栏不同
+                    draw_hint(data);
+                    update_data(data);
+                    draw_board(data);
+                },
+                Some("tick") if playing => {
+                    sync::apply(data, &message)?;
+                    update_data(data);
+                    draw_board(data);
+                },
+                Some("over") => {
+                    move_cursor(25, 5);
+                    set_color(data.colors.white);//调成白色
+                    addstr(message.get("message").and_then(Json::as_str).unwrap_or(""));
+                    refresh().unwrap();
+                },
+                Some("menu") => {
+                    playing = false;
+                    clear_screen();
+                    notice(data, "玩家正在选关,等待下一局");
+                },
+                _ => {}
+            }
+        }
+        timeout(Duration::from_millis(20)).unwrap(); //等待按键的同时检查收到的消息
+        let key = getch();
+        nodelay(stdscr(), false).unwrap();
+        match key {
+            Ok(CharacterResult::Character('\u{001B}')) | Ok(CharacterResult::Character('q')) | Ok(CharacterResult::Key(KeyBinding::Exit)) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+fn draw_hint(data: &GameData) {//左下角提示正在观战
+    move_cursor(0, 24);
+    set_color(data.colors.white);//调成白色
+    addstr("观战中 Esc：退出");
+}